@@ -23,7 +23,7 @@
 ***************************************************************************** */
 use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
 
-/**
+/*
  * Ok, this module is very unsafe. This still needs a lot of testing.
  */
 
@@ -110,7 +110,7 @@ impl BlackBox {
                 Ok(&(*content_t_ptr))
             }
         } else {
-            Err(BugeError::new(BugeErrorType::NotCompatible, &format!("Incorrect unboxing type")))
+            Err(BugeError::new(BugeErrorType::NotCompatible, "Incorrect unboxing type"))
         }
     }
 
@@ -127,7 +127,7 @@ impl BlackBox {
                 Ok(&mut (*content_t_ptr))
             }
         } else {
-            Err(BugeError::new(BugeErrorType::NotCompatible, &format!("Incorrect unboxing type")))
+            Err(BugeError::new(BugeErrorType::NotCompatible, "Incorrect unboxing type"))
         }
     }
 
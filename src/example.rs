@@ -21,9 +21,40 @@
  OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
  SOFTWARE.
 ***************************************************************************** */
-use bugeutils::list::{ReusableIndexVec, ID};
+use bugeutils::list::{ReusableIndexNode, ReusableIndexVec, UntypedID};
 
 use std::io;
+use std::time::Instant;
+
+// Compares iterating a mostly empty vector node by node against its iterator, which skips the
+// runs of removed slots.
+fn bench_sparse_iteration(len: usize) {
+    let mut sparse = ReusableIndexVec::<u64>::with_capacity(len);
+    let ids: Vec<_> = (0..len as u64).map(|i| sparse.add(i)).collect();
+    for (i, id) in ids.into_iter().enumerate() {
+        if i % 10 != 0 {
+            sparse.remove(id).unwrap();
+        }
+    }
+
+    let start = Instant::now();
+    let naive: u64 = sparse.as_slice().iter()
+        .filter_map(|node| match node {
+            ReusableIndexNode::Exists(_, val) => Some(*val),
+            _ => None,
+        })
+        .sum();
+    let naive_time = start.elapsed();
+
+    let start = Instant::now();
+    let skipping: u64 = sparse.iter().sum();
+    let skipping_time = start.elapsed();
+
+    assert_eq!(naive, skipping);
+    println!("{} slots, {} live ({}% empty)", len, sparse.len(), 100 - sparse.len() * 100 / len);
+    println!("node by node: {:?}", naive_time);
+    println!("iter():       {:?}", skipping_time);
+}
 
 pub fn main() {
     println!("It works!");
@@ -58,7 +89,7 @@ pub fn main() {
                     0
                 });
 
-                if let Err(e) = mylist.remove(ID(cycle_stamp, index)) {
+                if let Err(e) = mylist.remove(UntypedID(cycle_stamp, index).typed()) {
                     println!("ERROR: {}", e);
                 }
             },
@@ -76,7 +107,7 @@ pub fn main() {
                     0
                 });
 
-                match mylist.get(ID(cycle_stamp, index)) {
+                match mylist.get(UntypedID(cycle_stamp, index).typed()) {
                     Some(val) => {
                         println!("GOT={}", val);
                     },
@@ -88,6 +119,10 @@ pub fn main() {
             "SHW" => {
                 println!("{:?}", mylist);
             },
+            "BENCH" => {
+                let len = input.get(1).and_then(|len| len.trim().parse().ok()).filter(|len| *len > 0).unwrap_or(1_000_000);
+                bench_sparse_iteration(len);
+            },
             "BYE" => break,
             _ => (),
         }
@@ -23,13 +23,25 @@
 ***************************************************************************** */
 pub type ListResult<T> = Result<T, crate::error::Error>;
 
+/// The index of a slot inside of a vector.
 pub type Index = usize;
+/// The cycle stamp used by the default key, `UntypedID`.
 pub type CycleStamp = u32;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub struct ID(pub CycleStamp, pub Index);
-
+mod id;
+pub use self::id::*;
+mod key;
+pub use self::key::*;
+mod occupancy;
 mod reusable_index_vec;
 pub use self::reusable_index_vec::*;
 mod reusable_index_multivec;
 pub use self::reusable_index_multivec::*;
+mod secondary_map;
+pub use self::secondary_map::*;
+mod dense_reusable_index_vec;
+pub use self::dense_reusable_index_vec::*;
+mod split_reusable_index_vec;
+pub use self::split_reusable_index_vec::*;
+mod chunked_reusable_index_vec;
+pub use self::chunked_reusable_index_vec::*;
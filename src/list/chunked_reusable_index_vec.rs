@@ -0,0 +1,767 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::occupancy::{next_occupied, prev_occupied, Occupancy};
+use crate::list::reusable_index_vec::{check_fresh_index, check_status, free_list_head, slot_status, FreeListHead, RemovalLog};
+use crate::list::{ExhaustionPolicy, Index, Key, KeyIndex, ListResult, ReusableIndexAddError, ReusableIndexNode, Stamp, UntypedID, ID};
+
+use std::iter::{Flatten, FusedIterator};
+use std::pin::Pin;
+use std::{fmt, mem, ops, ptr, slice};
+
+const DEFAULT_CHUNK_LEN: usize = 256;
+
+// The nodes of every chunk, in order.
+type ChunkNodesMut<'vec, T, K> = Flatten<slice::IterMut<'vec, Vec<ReusableIndexNode<T, K>>>>;
+
+/// A `ReusableIndexVec` that keeps its nodes in chunks of a fixed size, which never move.
+///
+/// A `ReusableIndexVec` reallocates its nodes when it grows, moving every element. Here a new
+/// chunk is allocated instead, and the old ones stay where they are, so growing never copies the
+/// elements and an element keeps its address from the moment it is added until it is removed.
+/// Pointers to the elements may be handed out to FFI code, for as long as the elements exist.
+///
+/// Removing an element drops it where it is, so elements that are not `Unpin` can be used pinned
+/// through `get_pin_mut` and `iter_pin_mut`. The functions that could move an element out, such
+/// as `get_mut` and `take`, are only there when `T` is `Unpin`.
+///
+/// ```
+/// use bugeutils::list::ChunkedReusableIndexVec;
+///
+/// let mut names = ChunkedReusableIndexVec::with_chunk_len(2);
+/// let alice = names.add(String::from("Alice"));
+/// let address = names.get(alice).unwrap() as *const String;
+///
+/// for i in 0..100 {
+///     names.add(i.to_string());
+/// }
+/// assert_eq!(names.get(alice).unwrap() as *const String, address);
+///
+/// names.remove(alice).unwrap();
+/// assert_eq!(names.get(alice), None);
+/// ```
+pub struct ChunkedReusableIndexVec<T, K: Key = UntypedID> {
+    // Every chunk is allocated with a capacity of `chunk_len` and never grows past it, so it never
+    // reallocates. Only the last one is not full.
+    chunks: Vec<Vec<ReusableIndexNode<T, K>>>,
+    chunk_len: usize,
+    last_removed: Option<Index>,
+    live_count: usize,
+    exhaustion_policy: ExhaustionPolicy,
+    // Which slots hold an element, so that iterating can skip the others quickly.
+    occupied: Occupancy,
+    // Where each slot was last freed, to tell in the errors for expired IDs.
+    removed_at: RemovalLog,
+}
+
+impl<T> ChunkedReusableIndexVec<T> {
+    #[inline]
+    /// Creates a new empty `ChunkedReusableIndexVec`.
+    pub fn new() -> Self {
+        Self::with_key()
+    }
+
+    #[inline]
+    /// Creates a new empty `ChunkedReusableIndexVec` that allocates `chunk_len` nodes at a time.
+    ///
+    /// # Panics
+    /// Panics if `chunk_len` is zero.
+    pub fn with_chunk_len(chunk_len: usize) -> Self {
+        Self::with_chunk_len_and_key(chunk_len)
+    }
+}
+
+impl<T, K: Key> ChunkedReusableIndexVec<T, K> {
+    #[inline]
+    /// Creates a new empty `ChunkedReusableIndexVec` that uses the key `K` for its IDs.
+    pub fn with_key() -> Self {
+        Self::with_chunk_len_and_key(DEFAULT_CHUNK_LEN)
+    }
+
+    /// Creates a new empty `ChunkedReusableIndexVec` that uses the key `K` for its IDs, and
+    /// allocates `chunk_len` nodes at a time.
+    ///
+    /// # Panics
+    /// Panics if `chunk_len` is zero.
+    pub fn with_chunk_len_and_key(chunk_len: usize) -> Self {
+        assert!(chunk_len > 0, "A ChunkedReusableIndexVec cannot have empty chunks");
+
+        Self {
+            chunks: Vec::new(),
+            chunk_len,
+            last_removed: None,
+            live_count: 0,
+            exhaustion_policy: ExhaustionPolicy::default(),
+            occupied: Occupancy::default(),
+            removed_at: RemovalLog::default(),
+        }
+    }
+
+    #[inline]
+    /// Returns the number of existing elements.
+    pub fn len(&self) -> usize {
+        self.live_count
+    }
+
+    #[inline]
+    /// Returns `true` if there are no existing elements.
+    pub fn is_empty(&self) -> bool {
+        self.live_count == 0
+    }
+
+    #[inline]
+    /// Returns how many nodes each chunk holds.
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_len
+    }
+
+    #[inline]
+    /// Returns how many chunks have been allocated.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    #[inline]
+    /// Returns what is done with slots that run out of cycle stamps.
+    pub fn exhaustion_policy(&self) -> ExhaustionPolicy {
+        self.exhaustion_policy
+    }
+
+    #[inline]
+    /// Changes what is done with slots that run out of cycle stamps.
+    pub fn set_exhaustion_policy(&mut self, policy: ExhaustionPolicy) {
+        self.exhaustion_policy = policy;
+    }
+
+    #[inline]
+    // The number of slots, existing or not.
+    fn slot_count(&self) -> usize {
+        match self.chunks.last() {
+            Some(last) => (self.chunks.len() - 1) * self.chunk_len + last.len(),
+            None => 0,
+        }
+    }
+
+    #[inline]
+    fn node(&self, index: Index) -> Option<&ReusableIndexNode<T, K>> {
+        self.chunks.get(index / self.chunk_len)?.get(index % self.chunk_len)
+    }
+
+    #[inline]
+    fn node_mut(&mut self, index: Index) -> Option<&mut ReusableIndexNode<T, K>> {
+        let chunk_len = self.chunk_len;
+        self.chunks.get_mut(index / chunk_len)?.get_mut(index % chunk_len)
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error.
+    pub fn add(&mut self, node: T) -> ID<T, K> {
+        match self.try_add(node) {
+            Ok(id) => id,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_add`.
+    pub fn try_add(&mut self, node: T) -> Result<ID<T, K>, ReusableIndexAddError<T>> {
+        while let Some(last_removed) = self.last_removed {
+            let slot = self.node(last_removed).expect("[LOGIC ERROR] Last removed index is out of bounds!");
+            let new_cycle_stamp = match free_list_head(slot, last_removed, self.exhaustion_policy) {
+                Ok((FreeListHead::Reuse(new_cycle_stamp), next_removed)) => {
+                    self.last_removed = next_removed;
+                    new_cycle_stamp
+                },
+                Ok((FreeListHead::Retire(cycle_stamp), next_removed)) => {
+                    // The slot has run out of stamps, it must never be used again.
+                    *self.node_mut(last_removed).expect("[LOGIC ERROR] Node should exist") = ReusableIndexNode::Retired(cycle_stamp);
+                    self.last_removed = next_removed;
+                    continue;
+                },
+                // The slot stays at the head of the free list, so that every call reports it.
+                Err(error) => return Err(ReusableIndexAddError::new(error, node)),
+            };
+
+            *self.node_mut(last_removed).expect("[LOGIC ERROR] Node should exist") = ReusableIndexNode::Exists(new_cycle_stamp, node);
+            self.occupied.set(last_removed);
+            self.live_count += 1;
+            return Ok(ID::from_untyped(K::new(new_cycle_stamp, last_removed)));
+        }
+
+        let added_at_index = self.slot_count();
+        if let Err(error) = check_fresh_index::<K>(added_at_index) {
+            return Err(ReusableIndexAddError::new(error, node));
+        }
+
+        let offset = added_at_index % self.chunk_len;
+        if offset == 0 {
+            // Every chunk is full, or there are none yet. The old chunks stay where they are, only
+            // their handles move.
+            self.chunks.push(Vec::with_capacity(self.chunk_len));
+        }
+        let chunk = self.chunks.last_mut().expect("[LOGIC ERROR] There should be a chunk with room");
+        debug_assert!(chunk.len() < chunk.capacity(), "[LOGIC ERROR] A chunk would reallocate");
+        chunk.push(ReusableIndexNode::Exists(K::Stamp::ZERO, node));
+
+        self.occupied.set(added_at_index);
+        self.live_count += 1;
+        Ok(ID::from_untyped(K::new(K::Stamp::ZERO, added_at_index)))
+    }
+
+    /// Removes the element associated with the given ID, dropping it where it is.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::remove`.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn remove(&mut self, id: ID<T, K>) -> ListResult<()> {
+        self.check_id(id)?;
+        let removed_node = self.vacate(id);
+
+        let node: *mut ReusableIndexNode<T, K> = self.node_mut(id.index()).expect("[LOGIC ERROR] Checked node should exist");
+        let _removed = RemovedOnDrop { node, removed_node: Some(removed_node) };
+        // The element may be pinned, so it is dropped without being moved. The guard then writes
+        // the removed node in its place, even if dropping the element panics.
+        unsafe { ptr::drop_in_place(node) };
+
+        Ok(())
+    }
+
+    // Takes the slot of a checked ID out of use, returning the node that is to replace it.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    fn vacate(&mut self, id: ID<T, K>) -> ReusableIndexNode<T, K> {
+        let (cycle_stamp, index) = (id.stamp(), id.index());
+        self.occupied.clear(index);
+        self.live_count -= 1;
+        #[cfg(all(feature = "debug-removals", debug_assertions))]
+        self.removed_at.record(index);
+
+        if self.exhaustion_policy == ExhaustionPolicy::Retire && cycle_stamp == K::MAX_STAMP {
+            // The slot cannot be reused without wrapping, so it does not go back to the free list.
+            return ReusableIndexNode::Retired(cycle_stamp);
+        }
+
+        let next_removed = self.last_removed.replace(index);
+        match next_removed {
+            Some(next_removed) => ReusableIndexNode::RemovedAndNext(cycle_stamp, K::Index::from_index(next_removed)),
+            None => ReusableIndexNode::Removed(cycle_stamp),
+        }
+    }
+
+    #[inline]
+    fn check_id(&self, id: ID<T, K>) -> ListResult<()> {
+        check_status(id, slot_status(self.node(id.index()), id.stamp(), self.slot_count()), &self.removed_at)
+    }
+
+    #[inline]
+    /// Returns true if the given ID is associated with an element of the vector.
+    pub fn contains(&self, id: ID<T, K>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get(&self, id: ID<T, K>) -> Option<&T> {
+        match self.node(id.index())? {
+            ReusableIndexNode::Exists(cycle_stamp, node) if *cycle_stamp == id.stamp() => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get`.
+    pub fn try_get(&self, id: ID<T, K>) -> ListResult<&T> {
+        self.check_id(id)?;
+        Ok(self.get(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
+    /// Returns a pinned mutable reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get_pin_mut(&mut self, id: ID<T, K>) -> Option<Pin<&mut T>> {
+        match self.node_mut(id.index())? {
+            // The element is never moved until it is dropped, see `remove`.
+            ReusableIndexNode::Exists(cycle_stamp, node) if *cycle_stamp == id.stamp() => Some(unsafe { Pin::new_unchecked(node) }),
+            _ => None,
+        }
+    }
+
+    /// Returns a pinned mutable reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get_mut`.
+    pub fn try_get_pin_mut(&mut self, id: ID<T, K>) -> ListResult<Pin<&mut T>> {
+        self.check_id(id)?;
+        Ok(self.get_pin_mut(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
+    #[inline]
+    /// Returns an iterator on the list of existing elements.
+    pub fn iter(&self) -> ChunkedReusableIndexIterator<'_, T, K> {
+        ChunkedReusableIndexIterator { inner: self.iter_with_ids() }
+    }
+
+    #[inline]
+    /// Returns an iterator on the list of existing elements, yielding each one with its ID.
+    pub fn iter_with_ids(&self) -> ChunkedReusableIndexIdIterator<'_, T, K> {
+        ChunkedReusableIndexIdIterator {
+            chunks: &self.chunks,
+            chunk_len: self.chunk_len,
+            occupied: self.occupied.words(),
+            front: 0,
+            back: self.slot_count(),
+            remaining: self.live_count,
+        }
+    }
+
+    #[inline]
+    /// Returns an iterator of pinned mutable references on the list of existing elements,
+    /// yielding each one with its ID.
+    pub fn iter_pin_mut(&mut self) -> ChunkedReusableIndexPinIteratorMut<'_, T, K> {
+        ChunkedReusableIndexPinIteratorMut {
+            back: self.slot_count(),
+            remaining: self.live_count,
+            nodes: self.chunks.iter_mut().flatten(),
+            occupied: self.occupied.words(),
+            front: 0,
+        }
+    }
+} // End of impl ChunkedReusableIndexVec
+
+impl<T: Unpin, K: Key> ChunkedReusableIndexVec<T, K> {
+    /// Removes the element associated with the given ID, giving it back.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::take`.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn take(&mut self, id: ID<T, K>) -> ListResult<T> {
+        self.check_id(id)?;
+        let removed_node = self.vacate(id);
+
+        let node = self.node_mut(id.index()).expect("[LOGIC ERROR] Checked node should exist");
+        match mem::replace(node, removed_node) {
+            ReusableIndexNode::Exists(_, node) => Ok(node),
+            _ => panic!("[LOGIC ERROR] Checked node should exist"),
+        }
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    #[inline]
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut T> {
+        self.get_pin_mut(id).map(Pin::into_inner)
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get_mut`.
+    #[inline]
+    pub fn try_get_mut(&mut self, id: ID<T, K>) -> ListResult<&mut T> {
+        self.try_get_pin_mut(id).map(Pin::into_inner)
+    }
+
+    #[inline]
+    /// Returns an iterator of mutable references on the list of existing elements.
+    pub fn iter_mut(&mut self) -> ChunkedReusableIndexIteratorMut<'_, T, K> {
+        ChunkedReusableIndexIteratorMut { inner: self.iter_pin_mut() }
+    }
+}
+
+// Writes the removed node over a node whose element has been dropped in place.
+struct RemovedOnDrop<T, K: Key> {
+    node: *mut ReusableIndexNode<T, K>,
+    removed_node: Option<ReusableIndexNode<T, K>>,
+}
+
+impl<T, K: Key> Drop for RemovedOnDrop<T, K> {
+    fn drop(&mut self) {
+        if let Some(removed_node) = self.removed_node.take() {
+            // The old node has been dropped already, so it must not be dropped again.
+            unsafe { ptr::write(self.node, removed_node) };
+        }
+    }
+}
+
+impl<T, K: Key> Default for ChunkedReusableIndexVec<T, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<T: fmt::Debug, K: Key> fmt::Debug for ChunkedReusableIndexVec<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.chunks.iter().flatten()).finish()
+    }
+}
+
+impl<T, K: Key> ops::Index<ID<T, K>> for ChunkedReusableIndexVec<T, K> {
+    type Output = T;
+
+    fn index(&self, id: ID<T, K>) -> &T {
+        match self.try_get(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for ChunkedReusableIndexVec: {}", err),
+        }
+    }
+}
+
+impl<T: Unpin, K: Key> ops::IndexMut<ID<T, K>> for ChunkedReusableIndexVec<T, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut T {
+        match self.try_get_mut(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for ChunkedReusableIndexVec: {}", err),
+        }
+    }
+}
+
+/// An iterator over the IDs of the existing elements of a `ChunkedReusableIndexVec`, together
+/// with references to them.
+pub struct ChunkedReusableIndexIdIterator<'vec, T, K: Key = UntypedID> {
+    chunks: &'vec [Vec<ReusableIndexNode<T, K>>],
+    chunk_len: usize,
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> ChunkedReusableIndexIdIterator<'vec, T, K> {
+    #[inline]
+    fn entry_at(&mut self, index: Index) -> (ID<T, K>, &'vec T) {
+        match &self.chunks[index / self.chunk_len][index % self.chunk_len] {
+            ReusableIndexNode::Exists(cycle_stamp, node) => {
+                self.remaining -= 1;
+                (ID::from_untyped(K::new(*cycle_stamp, index)), node)
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ChunkedReusableIndexIdIterator<'vec, T, K> {
+    type Item = (ID<T, K>, &'vec T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        self.front = index + 1;
+
+        Some(self.entry_at(index))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ChunkedReusableIndexIdIterator<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        self.back = index;
+
+        Some(self.entry_at(index))
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ChunkedReusableIndexIdIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ChunkedReusableIndexIdIterator<'vec, T, K> {}
+
+/// An iterator over references to the existing elements of a `ChunkedReusableIndexVec`.
+pub struct ChunkedReusableIndexIterator<'vec, T, K: Key = UntypedID> {
+    inner: ChunkedReusableIndexIdIterator<'vec, T, K>,
+}
+
+impl<'vec, T, K: Key> Iterator for ChunkedReusableIndexIterator<'vec, T, K> {
+    type Item = &'vec T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, node)| node)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ChunkedReusableIndexIterator<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, node)| node)
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ChunkedReusableIndexIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ChunkedReusableIndexIterator<'vec, T, K> {}
+
+/// An iterator over the IDs of the existing elements of a `ChunkedReusableIndexVec`, together
+/// with pinned mutable references to them.
+pub struct ChunkedReusableIndexPinIteratorMut<'vec, T, K: Key = UntypedID> {
+    // Holds the nodes from `front` up to, not including, `back`.
+    nodes: ChunkNodesMut<'vec, T, K>,
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> ChunkedReusableIndexPinIteratorMut<'vec, T, K> {
+    #[inline]
+    fn entry_at(&mut self, index: Index, node: Option<&'vec mut ReusableIndexNode<T, K>>) -> (ID<T, K>, Pin<&'vec mut T>) {
+        match node {
+            Some(ReusableIndexNode::Exists(cycle_stamp, node)) => {
+                self.remaining -= 1;
+                // The element is never moved until it is dropped, see `remove`.
+                (ID::from_untyped(K::new(*cycle_stamp, index)), unsafe { Pin::new_unchecked(node) })
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ChunkedReusableIndexPinIteratorMut<'vec, T, K> {
+    type Item = (ID<T, K>, Pin<&'vec mut T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        let node = self.nodes.nth(index - self.front);
+        self.front = index + 1;
+
+        Some(self.entry_at(index, node))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ChunkedReusableIndexPinIteratorMut<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        let node = self.nodes.nth_back(self.back - 1 - index);
+        self.back = index;
+
+        Some(self.entry_at(index, node))
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ChunkedReusableIndexPinIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ChunkedReusableIndexPinIteratorMut<'vec, T, K> {}
+
+/// An iterator over mutable references to the existing elements of a `ChunkedReusableIndexVec`,
+/// for elements that are `Unpin`.
+pub struct ChunkedReusableIndexIteratorMut<'vec, T, K: Key = UntypedID> {
+    inner: ChunkedReusableIndexPinIteratorMut<'vec, T, K>,
+}
+
+impl<'vec, T: Unpin, K: Key> Iterator for ChunkedReusableIndexIteratorMut<'vec, T, K> {
+    type Item = &'vec mut T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, node)| Pin::into_inner(node))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'vec, T: Unpin, K: Key> DoubleEndedIterator for ChunkedReusableIndexIteratorMut<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, node)| Pin::into_inner(node))
+    }
+}
+
+impl<'vec, T: Unpin, K: Key> ExactSizeIterator for ChunkedReusableIndexIteratorMut<'vec, T, K> {}
+
+impl<'vec, T: Unpin, K: Key> FusedIterator for ChunkedReusableIndexIteratorMut<'vec, T, K> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType as BugeErrorType;
+    use std::cell::Cell;
+    use std::marker::PhantomPinned;
+    use std::rc::Rc;
+
+    // Remembers where it was pinned, and checks it is dropped from there.
+    struct Anchored {
+        pinned_at: Cell<usize>,
+        drops: Rc<Cell<usize>>,
+        _pin: PhantomPinned,
+    }
+
+    impl Anchored {
+        fn new(drops: &Rc<Cell<usize>>) -> Self {
+            Self { pinned_at: Cell::new(0), drops: Rc::clone(drops), _pin: PhantomPinned }
+        }
+
+        fn pin(self: Pin<&mut Self>) {
+            self.pinned_at.set(&*self as *const Self as usize);
+        }
+    }
+
+    impl Drop for Anchored {
+        fn drop(&mut self) {
+            let pinned_at = self.pinned_at.get();
+            assert!(pinned_at == 0 || pinned_at == self as *const Self as usize, "A pinned element has moved");
+            self.drops.set(self.drops.get() + 1);
+        }
+    }
+
+    #[test]
+    fn chunked_test() {
+        let mut chunked = ChunkedReusableIndexVec::with_chunk_len(4);
+        let ids: Vec<_> = (0..10).map(|i| chunked.add(i)).collect();
+        assert_eq!(chunked.chunk_count(), 3);
+        assert_eq!(chunked.len(), 10);
+
+        let addresses: Vec<_> = ids.iter().map(|id| &chunked[*id] as *const i32).collect();
+        chunked.remove(ids[1]).unwrap();
+        assert_eq!(chunked.take(ids[6]).unwrap(), 6);
+        for i in 10..100 {
+            chunked.add(i);
+        }
+        for (id, address) in ids.iter().zip(addresses) {
+            if let Some(node) = chunked.get(*id) {
+                assert_eq!(node as *const i32, address);
+            }
+        }
+
+        // The slots are reused as in a `ReusableIndexVec`.
+        let mut chunked = ChunkedReusableIndexVec::with_chunk_len(4);
+        let ids: Vec<_> = (0..10).map(|i| chunked.add(i)).collect();
+        chunked.remove(ids[3]).unwrap();
+        chunked.remove(ids[8]).unwrap();
+        let reused = chunked.add(30);
+        assert_eq!((reused.index(), reused.stamp()), (8, 1));
+        assert_eq!(chunked.get(ids[8]), None);
+        assert_eq!(chunked.try_get(ids[8]).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(chunked.try_get(ids[3]).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(chunked.try_get(ID::from_untyped(UntypedID(0, 10))).unwrap_err().error_type(), BugeErrorType::NotFound);
+
+        chunked[reused] += 1;
+        for node in chunked.iter_mut() {
+            *node *= 2;
+        }
+        assert_eq!(chunked.iter().copied().collect::<Vec<_>>(), vec![0, 2, 4, 8, 10, 12, 14, 62, 18]);
+        assert_eq!(chunked.iter_with_ids().next_back().map(|(id, _)| id), Some(ids[9]));
+        assert_eq!(chunked.iter().rev().copied().collect::<Vec<_>>(), vec![18, 62, 14, 12, 10, 8, 4, 2, 0]);
+        assert_eq!(chunked.iter_mut().len(), 9);
+
+        // Both ends meet in the middle, across the chunks.
+        let mut iter = chunked.iter_pin_mut();
+        assert_eq!(iter.next().map(|(id, _)| id), Some(ids[0]));
+        assert_eq!(iter.next_back().map(|(id, _)| id), Some(ids[9]));
+        assert_eq!(iter.len(), 7);
+        assert_eq!(iter.rev().map(|(id, _)| id.index()).collect::<Vec<_>>(), vec![8, 7, 6, 5, 4, 2, 1]);
+    }
+
+    #[test]
+    fn chunked_sparse_iteration_test() {
+        let mut chunked = ChunkedReusableIndexVec::with_chunk_len(16);
+        let ids: Vec<_> = (0..300u32).map(|i| chunked.add(i)).collect();
+        for id in ids[1..130].iter().chain(&ids[140..299]) {
+            chunked.remove(*id).unwrap();
+        }
+        assert_eq!(chunked.take(ids[135]).unwrap(), 135);
+
+        // The runs of removed slots are skipped from either end, across the chunks.
+        let expected: Vec<u32> = [0].iter().copied().chain(130..135).chain(136..140).chain([299].iter().copied()).collect();
+        assert_eq!(chunked.iter().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(chunked.iter().rev().copied().collect::<Vec<_>>(), expected.iter().rev().copied().collect::<Vec<_>>());
+
+        // A reused slot is seen again.
+        let reused = chunked.add(1000);
+        assert_eq!(reused.index(), 135);
+        let mut iter = chunked.iter_pin_mut();
+        assert_eq!(iter.next_back().map(|(id, _)| id), Some(ids[299]));
+        assert_eq!(iter.next().map(|(id, _)| id), Some(ids[0]));
+        assert_eq!(iter.map(|(id, _)| id.index()).collect::<Vec<_>>(), (130..140).collect::<Vec<_>>());
+        assert_eq!(chunked.iter_with_ids().nth(6).map(|(id, _)| id), Some(reused));
+    }
+
+    #[test]
+    fn chunked_pin_test() {
+        let drops = Rc::new(Cell::new(0));
+        let mut chunked = ChunkedReusableIndexVec::with_chunk_len(2);
+
+        let ids: Vec<_> = (0..5).map(|_| chunked.add(Anchored::new(&drops))).collect();
+        for (_, node) in chunked.iter_pin_mut() {
+            node.pin();
+        }
+
+        chunked.remove(ids[2]).unwrap();
+        assert_eq!(drops.get(), 1);
+        for _ in 0..20 {
+            let id = chunked.add(Anchored::new(&drops));
+            chunked.get_pin_mut(id).unwrap().pin();
+        }
+        assert!(chunked.get_pin_mut(ids[2]).is_none());
+        assert!(chunked.try_get_pin_mut(ids[4]).is_ok());
+
+        drop(chunked);
+        assert_eq!(drops.get(), 25);
+    }
+}
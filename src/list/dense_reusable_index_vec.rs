@@ -0,0 +1,327 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{Index, Key, ListResult, ReusableIndexAddError, ReusableIndexVec, UntypedID, ID};
+
+use std::iter::{Copied, Zip};
+use std::{ops, slice};
+
+#[derive(Debug)]
+/// A `ReusableIndexVec` that keeps its elements packed together in a `Vec`, without holes.
+///
+/// Iterating goes over the elements only, as a slice, no matter how many were removed. In
+/// exchange, every lookup goes through a table from the IDs to the positions of the elements, and
+/// removing an element moves the last one into its place, so the order of the elements changes.
+/// The IDs behave exactly as in a `ReusableIndexVec`.
+///
+/// ```
+/// use bugeutils::list::DenseReusableIndexVec;
+///
+/// let mut velocities = DenseReusableIndexVec::new();
+/// let a = velocities.add(1.0);
+/// let b = velocities.add(2.0);
+/// let c = velocities.add(3.0);
+///
+/// velocities.remove(a).unwrap();
+/// assert_eq!(velocities.as_slice(), &[3.0, 2.0]);
+///
+/// for velocity in velocities.as_mut_slice() {
+///     *velocity *= 0.5;
+/// }
+/// assert_eq!(velocities.get(b), Some(&1.0));
+/// assert_eq!(velocities.get(c), Some(&1.5));
+/// assert_eq!(velocities.get(a), None);
+/// ```
+pub struct DenseReusableIndexVec<T, K: Key = UntypedID> {
+    // From the IDs to the positions in `values`. The IDs of the table are the IDs of the elements.
+    positions: ReusableIndexVec<Index, K>,
+    values: Vec<T>,
+    // The ID of the element at each position.
+    ids: Vec<ID<T, K>>,
+}
+
+impl<T> DenseReusableIndexVec<T> {
+    #[inline]
+    /// Creates a new empty `DenseReusableIndexVec`.
+    pub fn new() -> Self {
+        Self::with_key()
+    }
+
+    #[inline]
+    /// Creates a new empty `DenseReusableIndexVec` with a given initial capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+}
+
+impl<T, K: Key> DenseReusableIndexVec<T, K> {
+    #[inline]
+    /// Creates a new empty `DenseReusableIndexVec` that uses the key `K` for its IDs.
+    pub fn with_key() -> Self {
+        Self::from_positions(ReusableIndexVec::with_key(), 0)
+    }
+
+    #[inline]
+    /// Creates a new empty `DenseReusableIndexVec` that uses the key `K` for its IDs, with a given
+    /// initial capacity.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
+        Self::from_positions(ReusableIndexVec::with_capacity_and_key(capacity), capacity)
+    }
+
+    #[inline]
+    fn from_positions(positions: ReusableIndexVec<Index, K>, capacity: usize) -> Self {
+        Self {
+            positions,
+            values: Vec::with_capacity(capacity),
+            ids: Vec::with_capacity(capacity),
+        }
+    }
+
+    #[inline]
+    /// Returns the number of existing elements.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    /// Returns `true` if there are no existing elements.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error.
+    pub fn add(&mut self, node: T) -> ID<T, K> {
+        match self.try_add(node) {
+            Ok(id) => id,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_add`.
+    pub fn try_add(&mut self, node: T) -> Result<ID<T, K>, ReusableIndexAddError<T>> {
+        let id = match self.positions.try_add(self.values.len()) {
+            Ok(id) => Self::typed(id),
+            Err(error) => return Err(ReusableIndexAddError::new(error.into(), node)),
+        };
+        self.values.push(node);
+        self.ids.push(id);
+        Ok(id)
+    }
+
+    /// Removes the element associated with the given ID. The last element takes its place.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::remove`.
+    #[inline]
+    pub fn remove(&mut self, id: ID<T, K>) -> ListResult<()> {
+        self.take(id).map(|_| ())
+    }
+
+    /// Removes the element associated with the given ID, giving it back. The last element takes
+    /// its place.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::take`.
+    pub fn take(&mut self, id: ID<T, K>) -> ListResult<T> {
+        let position = self.positions.take(Self::table_id(id))?;
+
+        let node = self.values.swap_remove(position);
+        self.ids.swap_remove(position);
+        if let Some(moved_id) = self.ids.get(position) {
+            self.positions[Self::table_id(*moved_id)] = position;
+        }
+
+        Ok(node)
+    }
+
+    #[inline]
+    /// Returns true if the given ID is associated with an element.
+    pub fn contains(&self, id: ID<T, K>) -> bool {
+        self.positions.contains(Self::table_id(id))
+    }
+
+    #[inline]
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get(&self, id: ID<T, K>) -> Option<&T> {
+        self.positions.get(Self::table_id(id)).map(|position| &self.values[*position])
+    }
+
+    #[inline]
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut T> {
+        let position = *self.positions.get(Self::table_id(id))?;
+        Some(&mut self.values[position])
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get`.
+    pub fn try_get(&self, id: ID<T, K>) -> ListResult<&T> {
+        let position = *self.positions.try_get(Self::table_id(id))?;
+        Ok(&self.values[position])
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get_mut`.
+    pub fn try_get_mut(&mut self, id: ID<T, K>) -> ListResult<&mut T> {
+        let position = *self.positions.try_get(Self::table_id(id))?;
+        Ok(&mut self.values[position])
+    }
+
+    #[inline]
+    /// Returns the existing elements, packed together. Their order changes when elements are
+    /// removed.
+    pub fn as_slice(&self) -> &[T] {
+        &self.values
+    }
+
+    #[inline]
+    /// Returns the existing elements, packed together, as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    #[inline]
+    /// Returns the IDs of the existing elements, in the same order as `as_slice`.
+    pub fn ids(&self) -> &[ID<T, K>] {
+        &self.ids
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and references to the existing elements.
+    pub fn iter_with_ids(&self) -> Zip<Copied<slice::Iter<'_, ID<T, K>>>, slice::Iter<'_, T>> {
+        self.ids.iter().copied().zip(self.values.iter())
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and mutable references to the existing elements.
+    pub fn iter_mut_with_ids(&mut self) -> Zip<Copied<slice::Iter<'_, ID<T, K>>>, slice::IterMut<'_, T>> {
+        self.ids.iter().copied().zip(self.values.iter_mut())
+    }
+
+    #[inline]
+    fn typed(id: ID<Index, K>) -> ID<T, K> {
+        ID::from_untyped(id.untyped())
+    }
+
+    #[inline]
+    fn table_id(id: ID<T, K>) -> ID<Index, K> {
+        ID::from_untyped(id.untyped())
+    }
+}
+
+impl<T, K: Key> Default for DenseReusableIndexVec<T, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+/// Indexing with an ID that is not associated with any element panics, as it does with a
+/// `ReusableIndexVec`.
+impl<T, K: Key> ops::Index<ID<T, K>> for DenseReusableIndexVec<T, K> {
+    type Output = T;
+
+    fn index(&self, id: ID<T, K>) -> &T {
+        match self.try_get(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for DenseReusableIndexVec: {}", err),
+        }
+    }
+}
+
+impl<T, K: Key> ops::IndexMut<ID<T, K>> for DenseReusableIndexVec<T, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut T {
+        match self.try_get_mut(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for DenseReusableIndexVec: {}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType as BugeErrorType;
+    use crate::list::PackedID;
+
+    #[test]
+    fn dense_test() {
+        let mut dense_vec = DenseReusableIndexVec::new();
+        let ids: Vec<_> = (0..5).map(|number| dense_vec.add(number)).collect();
+        assert_eq!(dense_vec.as_slice(), &[0, 1, 2, 3, 4]);
+
+        assert_eq!(dense_vec.take(ids[1]).unwrap(), 1);
+        assert_eq!(dense_vec.as_slice(), &[0, 4, 2, 3]);
+        assert_eq!(dense_vec.ids(), &[ids[0], ids[4], ids[2], ids[3]]);
+        dense_vec.remove(ids[3]).unwrap();
+        assert_eq!(dense_vec.as_slice(), &[0, 4, 2]);
+        assert_eq!(dense_vec.len(), 3);
+
+        for (id, node) in dense_vec.iter_mut_with_ids() {
+            *node += 10 * id.index();
+        }
+        assert_eq!(dense_vec[ids[4]], 44);
+        assert_eq!(dense_vec.get(ids[2]), Some(&22));
+        assert!(!dense_vec.contains(ids[1]));
+
+        // The same ID semantics as a `ReusableIndexVec`.
+        let reused = dense_vec.add(100);
+        assert_eq!(reused.index(), 3);
+        assert_eq!(reused.stamp(), 1);
+        assert_eq!(dense_vec.try_get(ids[3]).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(dense_vec.remove(ids[1]).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(dense_vec.iter_with_ids().map(|(id, node)| (id, *node)).collect::<Vec<_>>(),
+            vec![(ids[0], 0), (ids[4], 44), (ids[2], 22), (reused, 100)]);
+
+        // Removing the last element moves nothing.
+        dense_vec.remove(reused).unwrap();
+        assert_eq!(dense_vec.as_slice(), &[0, 44, 22]);
+        *dense_vec.get_mut(ids[0]).unwrap() = 7;
+        assert_eq!(dense_vec.ids().iter().map(|id| dense_vec[*id]).collect::<Vec<_>>(), vec![7, 44, 22]);
+    }
+
+    #[test]
+    fn dense_other_key_test() {
+        let mut dense_vec = DenseReusableIndexVec::<&str, PackedID>::with_capacity_and_key(2);
+        let id = dense_vec.add("packed");
+        assert_eq!(std::mem::size_of_val(&id), 4);
+        dense_vec.as_mut_slice()[0] = "changed";
+        assert_eq!(dense_vec.try_get(id).unwrap(), &"changed");
+        assert!(dense_vec.remove(id).is_ok());
+        assert!(dense_vec.is_empty());
+    }
+}
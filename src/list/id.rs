@@ -0,0 +1,224 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{CycleStamp, Index, Key};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// An `ID` that has been erased of the element type it refers to.
+///
+/// This is the escape hatch for the cases where handles of different element types must be
+/// stored together, or sent somewhere that does not know about the types. It can be turned back
+/// into a typed `ID` with `UntypedID::typed`, at which point the compiler trusts the programmer
+/// to have picked the right type again.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UntypedID(pub CycleStamp, pub Index);
+
+impl UntypedID {
+    /// Gives back the element type to this `UntypedID`.
+    #[inline]
+    pub fn typed<T>(self) -> ID<T> {
+        ID::from_untyped(self)
+    }
+}
+
+impl fmt::Display for UntypedID {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.0, self.1)
+    }
+}
+
+/// The handle of an element inside of a `ReusableIndexVec<T, K>`.
+///
+/// The `ID` is a `Key`, a combination of a cycle stamp and an index, that also carries the type
+/// `T` of the element it refers to. This way an `ID` obtained from a `ReusableIndexVec<Mesh>`
+/// cannot be used on a `ReusableIndexVec<Texture>` by mistake.
+///
+/// ```compile_fail
+///     use bugeutils::list::ReusableIndexVec;
+///
+///     let mut numbers = ReusableIndexVec::<u32>::new();
+///     let mut names = ReusableIndexVec::<&str>::new();
+///
+///     let number_id = numbers.add(42);
+///     names.get(number_id); // Does not compile, `number_id` is an `ID<u32>`
+/// ```
+///
+/// The type is only a marker, so `ID<T, K>` is `Copy`, `Eq`, `Hash` and `Ord` no matter what `T`
+/// is, and it has the same size as `K`.
+pub struct ID<T, K: Key = UntypedID> {
+    key: K,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, K: Key> ID<T, K> {
+    /// Creates a typed `ID` out of an untyped key.
+    #[inline]
+    pub fn from_untyped(key: K) -> Self {
+        Self {
+            key,
+            marker: PhantomData,
+        }
+    }
+
+    /// Erases the element type of this `ID`, returning its key.
+    #[inline]
+    pub fn untyped(self) -> K {
+        self.key
+    }
+
+    /// The cycle stamp part of this `ID`.
+    #[inline]
+    pub fn stamp(self) -> K::Stamp {
+        self.key.stamp()
+    }
+
+    /// The index part of this `ID`.
+    #[inline]
+    pub fn index(self) -> Index {
+        self.key.index()
+    }
+}
+
+// The traits below are implemented by hand, as deriving them would require `T` to implement them
+// as well, even though there is no `T` inside of an `ID`.
+
+impl<T, K: Key> Clone for ID<T, K> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T, K: Key> Copy for ID<T, K> {}
+
+impl<T, K: Key> PartialEq for ID<T, K> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T, K: Key> Eq for ID<T, K> {}
+
+impl<T, K: Key> PartialOrd for ID<T, K> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T, K: Key> Ord for ID<T, K> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<T, K: Key> Hash for ID<T, K> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl<T, K: Key> fmt::Debug for ID<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ID({}, {})", self.key.stamp(), self.key.index())
+    }
+}
+
+impl<T, K: Key> fmt::Display for ID<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::{}", self.key.stamp(), self.key.index())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, K: Key + serde::Serialize> serde::Serialize for ID<T, K> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.key.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, K: Key + serde::Deserialize<'de>> serde::Deserialize<'de> for ID<T, K> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        K::deserialize(deserializer).map(ID::from_untyped)
+    }
+}
+
+impl<T> From<ID<T>> for UntypedID {
+    #[inline]
+    fn from(id: ID<T>) -> Self {
+        id.key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Not a single trait is implemented by this one.
+    struct Nothing;
+
+    #[test]
+    fn traits_without_bounds_test() {
+        use std::collections::HashSet;
+
+        let id_a: ID<Nothing> = UntypedID(0, 1).typed();
+        let id_b: ID<Nothing> = UntypedID(1, 0).typed();
+        let id_c = id_a;
+
+        assert_eq!(id_a, id_c);
+        assert_ne!(id_a, id_b);
+        assert!(id_a < id_b);
+
+        let set: HashSet<ID<Nothing>> = vec![id_a, id_b, id_c].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn untyped_round_trip_test() {
+        let id: ID<u32> = ID::from_untyped(UntypedID(3, 7));
+
+        assert_eq!(id.stamp(), 3);
+        assert_eq!(id.index(), 7);
+        assert_eq!(id.untyped(), UntypedID(3, 7));
+        assert_eq!(UntypedID::from(id).typed::<u32>(), id);
+        assert_eq!(format!("{}", id), "3::7");
+    }
+
+    #[test]
+    fn size_test() {
+        use crate::list::PackedID;
+        use std::mem;
+
+        assert_eq!(mem::size_of::<ID<u128>>(), mem::size_of::<UntypedID>());
+        assert_eq!(mem::size_of::<ID<u128, PackedID>>(), 4);
+    }
+}
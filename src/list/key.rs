@@ -0,0 +1,243 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{CycleStamp, Index, UntypedID};
+
+use std::fmt;
+use std::hash::Hash;
+
+/// Unsigned integers that can be used as the cycle stamp of a `Key`.
+pub trait Stamp: Copy + Eq + Ord + Hash + fmt::Debug + fmt::Display {
+    /// The stamp given to a slot the first time it is used.
+    const ZERO: Self;
+
+    /// Widens the stamp, mostly for reporting.
+    fn to_u64(self) -> u64;
+
+    /// Narrows a `u64` into the stamp. Bits that do not fit are discarded.
+    fn from_u64(value: u64) -> Self;
+}
+
+/// Unsigned integers that can be used to store indices inside of the nodes of a vector.
+pub trait KeyIndex: Copy + Eq + fmt::Debug {
+    /// Widens the stored index into a regular `Index`.
+    fn to_index(self) -> Index;
+
+    /// Narrows an `Index` into the stored index. The caller must make sure it fits.
+    fn from_index(index: Index) -> Self;
+}
+
+macro_rules! impl_stamp {
+    ($($int:ty),*) => {$(
+        impl Stamp for $int {
+            const ZERO: Self = 0;
+
+            #[inline]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
+            #[inline]
+            fn from_u64(value: u64) -> Self {
+                value as Self
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_key_index {
+    ($($int:ty),*) => {$(
+        impl KeyIndex for $int {
+            #[inline]
+            fn to_index(self) -> Index {
+                self as Index
+            }
+
+            #[inline]
+            fn from_index(index: Index) -> Self {
+                index as Self
+            }
+        }
+    )*};
+}
+
+impl_stamp!(u8, u16, u32, u64);
+impl_key_index!(u16, u32, u64, usize);
+
+/// Describes the layout of the IDs handed out by a `ReusableIndexVec`.
+///
+/// A `Key` is a combination of a cycle stamp and an index, but it is up to the implementor to
+/// decide how wide each part is and how they are stored. The stamp and index types are also used
+/// by `ReusableIndexNode`, so narrow keys make the nodes smaller as well.
+///
+/// Three layouts are provided:
+/// * `UntypedID`, the default, with a 32 bit stamp and a `usize` index.
+/// * `PackedID`, which packs a 20 bit index and a 12 bit stamp in a single `u32`.
+/// * `WideID`, with a 64 bit stamp that, in practice, never wraps.
+pub trait Key: Copy + Eq + Ord + Hash + fmt::Debug {
+    /// The type of the cycle stamp, as stored inside of the nodes.
+    type Stamp: Stamp;
+    /// The type used to link removed nodes together.
+    type Index: KeyIndex;
+
+    /// The largest cycle stamp this key can represent.
+    const MAX_STAMP: Self::Stamp;
+    /// The largest index this key can represent.
+    const MAX_INDEX: Index;
+
+    /// Creates a key out of its parts. `index` must not be larger than `MAX_INDEX`, and `stamp`
+    /// must not be larger than `MAX_STAMP`.
+    fn new(stamp: Self::Stamp, index: Index) -> Self;
+
+    /// The cycle stamp part of the key.
+    fn stamp(&self) -> Self::Stamp;
+
+    /// The index part of the key.
+    fn index(&self) -> Index;
+
+    /// Returns the stamp that comes after `stamp`, or `None` if `stamp` is already `MAX_STAMP`.
+    #[inline]
+    fn next_stamp(stamp: Self::Stamp) -> Option<Self::Stamp> {
+        if stamp == Self::MAX_STAMP {
+            None
+        } else {
+            Some(Self::Stamp::from_u64(stamp.to_u64() + 1))
+        }
+    }
+}
+
+impl Key for UntypedID {
+    type Stamp = CycleStamp;
+    type Index = Index;
+
+    const MAX_STAMP: CycleStamp = CycleStamp::MAX;
+    const MAX_INDEX: Index = Index::MAX;
+
+    #[inline]
+    fn new(stamp: CycleStamp, index: Index) -> Self {
+        UntypedID(stamp, index)
+    }
+
+    #[inline]
+    fn stamp(&self) -> CycleStamp {
+        self.0
+    }
+
+    #[inline]
+    fn index(&self) -> Index {
+        self.1
+    }
+}
+
+const PACKED_INDEX_BITS: u32 = 20;
+const PACKED_INDEX_MASK: u32 = (1 << PACKED_INDEX_BITS) - 1;
+
+/// A 32 bit key, with a 20 bit index and a 12 bit cycle stamp.
+///
+/// Useful when IDs are sent over the network. A vector using this key holds at most 2^20
+/// elements, and its slots wrap around (or retire) after 4096 reuses.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PackedID(pub u32);
+
+impl Key for PackedID {
+    type Stamp = u16;
+    type Index = u32;
+
+    const MAX_STAMP: u16 = (1 << (32 - PACKED_INDEX_BITS)) - 1;
+    const MAX_INDEX: Index = PACKED_INDEX_MASK as Index;
+
+    #[inline]
+    fn new(stamp: u16, index: Index) -> Self {
+        debug_assert!(stamp <= Self::MAX_STAMP, "Stamp {} does not fit in a PackedID", stamp);
+        debug_assert!(index <= Self::MAX_INDEX, "Index {} does not fit in a PackedID", index);
+
+        PackedID(((stamp as u32) << PACKED_INDEX_BITS) | (index as u32 & PACKED_INDEX_MASK))
+    }
+
+    #[inline]
+    fn stamp(&self) -> u16 {
+        (self.0 >> PACKED_INDEX_BITS) as u16
+    }
+
+    #[inline]
+    fn index(&self) -> Index {
+        (self.0 & PACKED_INDEX_MASK) as Index
+    }
+}
+
+/// A key with a 64 bit cycle stamp, for when a stamp should never wrap around.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WideID(pub u64, pub Index);
+
+impl Key for WideID {
+    type Stamp = u64;
+    type Index = Index;
+
+    const MAX_STAMP: u64 = u64::MAX;
+    const MAX_INDEX: Index = Index::MAX;
+
+    #[inline]
+    fn new(stamp: u64, index: Index) -> Self {
+        WideID(stamp, index)
+    }
+
+    #[inline]
+    fn stamp(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn index(&self) -> Index {
+        self.1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_id_test() {
+        let key = PackedID::new(PackedID::MAX_STAMP, PackedID::MAX_INDEX);
+
+        assert_eq!(key.0, u32::MAX);
+        assert_eq!(key.stamp(), 4095);
+        assert_eq!(key.index(), (1 << 20) - 1);
+
+        let key = PackedID::new(5, 1000);
+
+        assert_eq!(key.stamp(), 5);
+        assert_eq!(key.index(), 1000);
+    }
+
+    #[test]
+    fn next_stamp_test() {
+        assert_eq!(UntypedID::next_stamp(0), Some(1));
+        assert_eq!(UntypedID::next_stamp(u32::MAX), None);
+
+        assert_eq!(PackedID::next_stamp(4094), Some(4095));
+        assert_eq!(PackedID::next_stamp(4095), None);
+    }
+}
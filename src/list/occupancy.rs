@@ -0,0 +1,181 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::Index;
+
+const WORD_BITS: usize = u64::BITS as usize;
+
+// One bit for every slot of a `ReusableIndexVec`, a `SplitReusableIndexVec` or a
+// `ChunkedReusableIndexVec`, set when the slot holds an element. Iterating goes through the bits a word at a time, so a run of removed slots
+// is stepped over 64 slots at once instead of node by node.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Occupancy {
+    words: Vec<u64>,
+}
+
+impl Occupancy {
+    // Marks the slots that hold an element among `occupied`.
+    pub fn from_slots<I: IntoIterator<Item = bool>>(occupied: I) -> Self {
+        let mut occupancy = Self::default();
+        for (index, occupied) in occupied.into_iter().enumerate() {
+            if occupied {
+                occupancy.set(index);
+            }
+        }
+        occupancy
+    }
+
+    #[inline]
+    pub fn set(&mut self, index: Index) {
+        let word = index / WORD_BITS;
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << (index % WORD_BITS);
+    }
+
+    #[inline]
+    pub fn clear(&mut self, index: Index) {
+        if let Some(word) = self.words.get_mut(index / WORD_BITS) {
+            *word &= !(1 << (index % WORD_BITS));
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: Index) -> bool {
+        matches!(self.words.get(index / WORD_BITS), Some(word) if word & (1 << (index % WORD_BITS)) != 0)
+    }
+
+    // Forgets the slots from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        let tail_bits = len % WORD_BITS;
+        self.words.truncate(len / WORD_BITS + if tail_bits != 0 { 1 } else { 0 });
+        if tail_bits != 0 {
+            if let Some(last) = self.words.last_mut() {
+                *last &= (1 << tail_bits) - 1;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+}
+
+// Returns the first slot holding an element from `start` up to, not including, `end`.
+pub(super) fn next_occupied(words: &[u64], start: Index, end: Index) -> Option<Index> {
+    let mut index = start;
+    while index < end {
+        let word = match words.get(index / WORD_BITS) {
+            Some(word) => word >> (index % WORD_BITS),
+            None => return None,
+        };
+
+        if word != 0 {
+            let found = index + word.trailing_zeros() as usize;
+            return if found < end { Some(found) } else { None };
+        }
+        index = (index / WORD_BITS + 1) * WORD_BITS;
+    }
+
+    None
+}
+
+// Returns the last slot holding an element from `start` up to, not including, `end`.
+pub(super) fn prev_occupied(words: &[u64], start: Index, end: Index) -> Option<Index> {
+    let mut end = end.min(words.len() * WORD_BITS);
+    while end > start {
+        let last = end - 1;
+        let word = words[last / WORD_BITS] << (WORD_BITS - 1 - last % WORD_BITS);
+
+        if word != 0 {
+            let found = last - word.leading_zeros() as usize;
+            return if found >= start { Some(found) } else { None };
+        }
+        end = last / WORD_BITS * WORD_BITS;
+    }
+
+    None
+}
+
+// Returns the last slot before `end` that holds no element.
+pub(super) fn prev_vacant(words: &[u64], end: Index) -> Option<Index> {
+    let mut end = end;
+    if end > words.len() * WORD_BITS {
+        // The slots past the last word hold no element.
+        return Some(end - 1);
+    }
+
+    while end > 0 {
+        let last = end - 1;
+        let word = !words[last / WORD_BITS] << (WORD_BITS - 1 - last % WORD_BITS);
+
+        if word != 0 {
+            return Some(last - word.leading_zeros() as usize);
+        }
+        end = last / WORD_BITS * WORD_BITS;
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occupancy_test() {
+        let mut occupancy = Occupancy::default();
+        for index in [3, 64, 130, 200].iter() {
+            occupancy.set(*index);
+        }
+        assert!(occupancy.get(130) && !occupancy.get(131) && !occupancy.get(1000));
+        let words = occupancy.words();
+
+        assert_eq!(next_occupied(words, 0, 300), Some(3));
+        assert_eq!(next_occupied(words, 4, 300), Some(64));
+        assert_eq!(next_occupied(words, 65, 300), Some(130));
+        assert_eq!(next_occupied(words, 131, 200), None);
+        assert_eq!(next_occupied(words, 201, 1000), None);
+        assert_eq!(prev_occupied(words, 0, 300), Some(200));
+        assert_eq!(prev_occupied(words, 0, 200), Some(130));
+        assert_eq!(prev_occupied(words, 65, 130), None);
+        assert_eq!(prev_occupied(words, 64, 130), Some(64));
+        assert_eq!(prev_occupied(words, 0, 3), None);
+
+        assert_eq!(prev_vacant(words, 1000), Some(999));
+        assert_eq!(prev_vacant(words, 201), Some(199));
+        assert_eq!(prev_vacant(words, 4), Some(2));
+        assert_eq!(prev_vacant(&[u64::MAX, u64::MAX], 128), None);
+        assert_eq!(prev_vacant(&[u64::MAX, !1], 128), Some(64));
+
+        occupancy.clear(64);
+        assert_eq!(next_occupied(occupancy.words(), 4, 300), Some(130));
+        occupancy.truncate(131);
+        assert_eq!(occupancy.words().len(), 3);
+        assert_eq!(prev_occupied(occupancy.words(), 0, 1000), Some(130));
+        occupancy.truncate(130);
+        assert_eq!(prev_occupied(occupancy.words(), 0, 1000), Some(3));
+    }
+}
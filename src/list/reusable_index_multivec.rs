@@ -23,14 +23,16 @@
 ***************************************************************************** */
 use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
 
-use crate::list::{ListResult, CycleStamp, Index, ID};
+use crate::list::ListResult;
 
 use std::collections::HashMap;
+use std::collections::hash_map::Entry;
 use std::any::TypeId;
 
 pub struct ReusableIndexMultivec {
     //bookkeeper: Vec<
     vector_map: HashMap<TypeId, usize>,
+    #[allow(dead_code)]
     top_size: usize,
 }
 
@@ -38,12 +40,12 @@ impl ReusableIndexMultivec {
     pub fn insert_row<K>(&mut self) -> ListResult<()>
     where K: Sized + 'static {
         let id = TypeId::of::<K>();
-        if self.vector_map.contains_key(&id) {
-            Err(BugeError::new(BugeErrorType::InvalidParameter, &format!("Key already exists")))
-        } else {
-            let vec_on_heap = Box::new(Vec::<K>::new());
-            self.vector_map.insert(id, 0);
+        if let Entry::Vacant(entry) = self.vector_map.entry(id) {
+            let _vec_on_heap = Box::new(Vec::<K>::new());
+            entry.insert(0);
             Ok(())
+        } else {
+            Err(BugeError::new(BugeErrorType::InvalidParameter, "Key already exists"))
         }
     }
 
@@ -23,7 +23,37 @@
 ***************************************************************************** */
 use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
 
-use crate::list::{ListResult, CycleStamp, Index, ID};
+use crate::list::occupancy::{next_occupied, prev_vacant, Occupancy};
+use crate::list::{ListResult, Index, Key, KeyIndex, Stamp, UntypedID, ID};
+
+use std::collections::VecDeque;
+#[cfg(all(feature = "debug-removals", debug_assertions))]
+use std::panic::Location;
+use std::{fmt, mem};
+use std::ops;
+use std::sync::atomic::AtomicUsize;
+
+mod iter;
+pub use self::iter::*;
+mod cursor;
+pub use self::cursor::*;
+mod compact;
+pub use self::compact::*;
+mod entry;
+pub use self::entry::*;
+#[cfg(feature = "serde")]
+mod serialization;
+mod snapshot;
+pub use self::snapshot::*;
+mod reserve;
+use self::reserve::NO_INDEX;
+mod status;
+pub use self::status::*;
+mod integrity;
+use self::integrity::FreeListLayout;
+mod stats;
+pub use self::stats::*;
+mod bulk;
 
 /// This enum elaborates which kind of nodes will exist inside of the vector.
 /// 
@@ -42,14 +72,148 @@ use crate::list::{ListResult, CycleStamp, Index, ID};
 /// This is assuming the type `T` has been aligned to a 64 bit word. This is not an optimization on
 /// 32 bit machines, but it will still work. It was done because I figured doing it in some other
 /// ways was just very wasteful on memory, as there will be long vectors of this thing.
+///
+/// The widths of the stamp and of the stored index come from the `Key` `K`. The default one,
+/// `UntypedID`, gives the layout described above. Narrower keys, such as `PackedID`, make the
+/// node smaller. For `T` with a large alignment, or very small `T`, `SplitReusableIndexVec` keeps
+/// the stamps apart from the values instead.
 #[derive(Debug)]
-pub enum ReusableIndexNode<T> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(bound(
+    serialize = "T: serde::Serialize, K::Stamp: serde::Serialize, K::Index: serde::Serialize",
+    deserialize = "T: serde::Deserialize<'de>, K::Stamp: serde::Deserialize<'de>, K::Index: serde::Deserialize<'de>",
+)))]
+pub enum ReusableIndexNode<T, K: Key = UntypedID> {
     /// The value of type `T` exists. It is on the cycle `CycleStamp`.
-    Exists(CycleStamp, T),
+    Exists(K::Stamp, T),
     /// The value has been removed.
-    Removed(CycleStamp),
+    Removed(K::Stamp),
     /// The value has been removed. This is used for bookkeeping.
-    RemovedAndNext(CycleStamp, Index),
+    RemovedAndNext(K::Stamp, K::Index),
+    /// The value has been removed, and the slot has run out of cycle stamps. It will never be
+    /// used again.
+    Retired(K::Stamp),
+    /// The slot has been handed out by `ReusableIndexVec::reserve_id`, with this `CycleStamp`, and
+    /// waits for `ReusableIndexVec::flush_reserved` to fill it.
+    Reserved(K::Stamp),
+}
+
+impl<T, K: Key> ReusableIndexNode<T, K> {
+    #[inline]
+    /// Returns the cycle stamp of the node, whatever its state.
+    pub fn cycle_stamp(&self) -> K::Stamp {
+        match *self {
+            ReusableIndexNode::Exists(cycle_stamp, _) |
+            ReusableIndexNode::Removed(cycle_stamp) |
+            ReusableIndexNode::RemovedAndNext(cycle_stamp, _) |
+            ReusableIndexNode::Retired(cycle_stamp) |
+            ReusableIndexNode::Reserved(cycle_stamp) => cycle_stamp,
+        }
+    }
+}
+
+/// What a `ReusableIndexVec` does with a slot whose `CycleStamp` cannot grow any further.
+///
+/// Reusing such a slot with a stamp that went back to zero means that a very old `ID` may become
+/// valid again, pointing to an element it has nothing to do with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ExhaustionPolicy {
+    /// The stamp goes back to zero and the slot is reused. This is the default.
+    Wrap,
+    /// The slot is retired when its last element is removed. It never goes back to the free list.
+    Retire,
+    /// Whatever would hand out the exhausted slot fails instead: `try_add` and `try_reserve_id`
+    /// return an error, and `add`, `add_many`, `add_with`, `vacant_entry` and `reserve_id` panic.
+    /// The slot is neither reused nor retired, not even by `compact`, and stays on the free list,
+    /// so every later call that reaches it fails as well, until the policy is changed.
+    Error,
+}
+
+impl Default for ExhaustionPolicy {
+    #[inline]
+    fn default() -> Self {
+        ExhaustionPolicy::Wrap
+    }
+}
+
+/// The error of `ReusableIndexVec::try_add`, which gives back the element that could not be
+/// added.
+pub struct ReusableIndexAddError<T> {
+    error: BugeError,
+    value: T,
+}
+
+impl<T> ReusableIndexAddError<T> {
+    #[inline]
+    pub(crate) fn new(error: BugeError, value: T) -> Self {
+        Self { error, value }
+    }
+
+    #[inline]
+    /// Returns the type of the error.
+    pub fn error_type(&self) -> BugeErrorType {
+        self.error.error_type()
+    }
+
+    #[inline]
+    /// Returns the element that could not be added.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    #[inline]
+    /// Splits the error into the error itself and the element that could not be added.
+    pub fn into_parts(self) -> (BugeError, T) {
+        (self.error, self.value)
+    }
+}
+
+impl<T> From<ReusableIndexAddError<T>> for BugeError {
+    #[inline]
+    fn from(error: ReusableIndexAddError<T>) -> Self {
+        error.error
+    }
+}
+
+impl<T> fmt::Debug for ReusableIndexAddError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReusableIndexAddError").field("error", &self.error).finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Display for ReusableIndexAddError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T> std::error::Error for ReusableIndexAddError<T> {}
+
+/// Which removed slot a `ReusableIndexVec` reuses first. It is chosen when the vector is created.
+///
+/// Every policy keeps the free list threaded through the removed nodes, so the nodes are the same
+/// size whatever the policy is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FreeListPolicy {
+    /// The most recently removed slot is reused first. This is the default, and the fastest.
+    Lifo,
+    /// The slot that was removed the longest ago is reused first. A slot waits for every other
+    /// removed slot before it is reused, which spreads the cycle stamps over the slots and makes
+    /// it less likely for an old ID to match a reused slot.
+    Fifo,
+    /// The removed slot with the lowest index is reused first, which keeps the elements packed at
+    /// the start of the vector. The free list is kept sorted: removing an element looks for the
+    /// closest removed slot below it, skipping over occupied slots 64 at a time.
+    LowestIndexFirst,
+}
+
+impl Default for FreeListPolicy {
+    #[inline]
+    fn default() -> Self {
+        FreeListPolicy::Lifo
+    }
 }
 
 #[derive(Debug)]
@@ -69,12 +233,48 @@ pub enum ReusableIndexNode<T> {
 ///         println!("{}", some_string); // prints 'A string is added'
 ///     }
 /// ```
-pub struct ReusableIndexVec<T> {
-    vector: Vec<ReusableIndexNode<T>>,
+///
+/// The layout of the IDs can be changed with the `K` parameter, see `Key`.
+///
+/// ```
+///     use bugeutils::list::{PackedID, ReusableIndexVec};
+///
+///     let mut entity_vec = ReusableIndexVec::<&str, PackedID>::with_key();
+///
+///     let string_id = entity_vec.add("This ID fits in 32 bits");
+///
+///     assert_eq!(std::mem::size_of_val(&string_id), 4);
+/// ```
+///
+/// The elements move whenever the vector grows. When their addresses must stay the same, or they
+/// must be pinned, use a `ChunkedReusableIndexVec` instead.
+pub struct ReusableIndexVec<T, K: Key = UntypedID> {
+    vector: Vec<ReusableIndexNode<T, K>>,
     last_removed: Option<Index>,
+    // The end of the free list, where `FreeListPolicy::Fifo` puts the removed slots.
+    free_tail: Option<Index>,
+    free_list_policy: FreeListPolicy,
+    live_count: usize,
+    free_count: usize,
+    // The stamp given to brand new slots. It only grows past zero when slots are truncated, so
+    // that the IDs of the truncated slots do not become valid again.
+    fresh_stamp: K::Stamp,
+    exhaustion_policy: ExhaustionPolicy,
+    retired_count: usize,
+    // The free list head as seen by `reserve_id`, which walks it down from a shared reference.
+    // It is the same as `last_removed`, or `NO_INDEX` for `None`, while nothing is reserved.
+    reserve_head: AtomicUsize,
+    // The number of slots reserved past the end of the vector.
+    reserve_extra: AtomicUsize,
+    // The `Reserved` nodes, in the order they were reserved.
+    reserved: VecDeque<Index>,
+    // Which slots hold an element, so that iterating can skip the others quickly.
+    occupied: Occupancy,
+    // Where each slot was last freed, to tell in the errors for expired IDs.
+    removed_at: RemovalLog,
 }
 
-const DEFAULT_INITIAL_CAPACITY: usize = 128;
+pub(super) const DEFAULT_INITIAL_CAPACITY: usize = 128;
 
 impl<T> ReusableIndexVec<T> {
     #[inline]
@@ -87,98 +287,486 @@ impl<T> ReusableIndexVec<T> {
     /// Creates a new empty `ReusableIndexVec` with a given initial capacity. This does not feeds
     /// any elements on the struct, just pre-allocates them.
     pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+
+    #[inline]
+    /// Creates a new empty `ReusableIndexVec` that reuses the removed slots in the order given by
+    /// `policy`.
+    ///
+    /// ```
+    /// use bugeutils::list::{FreeListPolicy, ReusableIndexVec};
+    ///
+    /// let mut vec = ReusableIndexVec::with_free_list_policy(FreeListPolicy::Fifo);
+    /// let ids: Vec<_> = (0..3).map(|number| vec.add(number)).collect();
+    /// vec.remove(ids[2]).unwrap();
+    /// vec.remove(ids[0]).unwrap();
+    ///
+    /// // The slot removed first is reused first.
+    /// assert_eq!(vec.add(10).index(), 2);
+    /// ```
+    pub fn with_free_list_policy(policy: FreeListPolicy) -> Self {
+        Self::with_capacity_and_free_list_policy(DEFAULT_INITIAL_CAPACITY, policy)
+    }
+}
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    #[inline]
+    /// Creates a new empty `ReusableIndexVec` that uses the key `K` for its IDs.
+    pub fn with_key() -> Self {
+        Self::with_capacity_and_key(DEFAULT_INITIAL_CAPACITY)
+    }
+
+    #[inline]
+    /// Creates a new empty `ReusableIndexVec` that uses the key `K` for its IDs, with a given
+    /// initial capacity.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
+        Self::with_capacity_and_free_list_policy(capacity, FreeListPolicy::default())
+    }
+
+    /// Creates a new empty `ReusableIndexVec` that uses the key `K` for its IDs and reuses the
+    /// removed slots in the order given by `policy`, with a given initial capacity.
+    pub fn with_capacity_and_free_list_policy(capacity: usize, policy: FreeListPolicy) -> Self {
         Self {
             vector: Vec::with_capacity(capacity),
             last_removed: None,
+            free_tail: None,
+            free_list_policy: policy,
+            live_count: 0,
+            free_count: 0,
+            fresh_stamp: K::Stamp::ZERO,
+            exhaustion_policy: ExhaustionPolicy::default(),
+            retired_count: 0,
+            reserve_head: AtomicUsize::new(NO_INDEX),
+            reserve_extra: AtomicUsize::new(0),
+            reserved: VecDeque::new(),
+            occupied: Occupancy::default(),
+            removed_at: RemovalLog::default(),
         }
     }
 
+    #[inline]
+    /// Returns what is done with slots that run out of cycle stamps.
+    pub fn exhaustion_policy(&self) -> ExhaustionPolicy {
+        self.exhaustion_policy
+    }
+
+    #[inline]
+    /// Changes what is done with slots that run out of cycle stamps. Exhausted slots that are
+    /// already on the free list are dealt with according to the new policy when they are reached.
+    pub fn set_exhaustion_policy(&mut self, policy: ExhaustionPolicy) {
+        self.exhaustion_policy = policy;
+    }
+
+    #[inline]
+    /// Returns the order in which removed slots are reused.
+    pub fn free_list_policy(&self) -> FreeListPolicy {
+        self.free_list_policy
+    }
+
+    #[inline]
+    /// Returns how many slots have been retired for running out of cycle stamps.
+    pub fn retired_slot_count(&self) -> usize {
+        self.retired_count
+    }
+
+    #[inline]
+    /// Returns the number of existing elements.
+    pub fn len(&self) -> usize {
+        self.live_count
+    }
+
+    #[inline]
+    /// Returns `true` if there are no existing elements.
+    pub fn is_empty(&self) -> bool {
+        self.live_count == 0
+    }
+
+    #[inline]
+    /// Returns the number of slots in the vector, existing or not.
+    pub fn slot_count(&self) -> usize {
+        self.vector.len()
+    }
+
+    #[inline]
+    /// Returns the number of removed slots waiting to be reused. Retired slots are not counted.
+    pub fn free_slot_count(&self) -> usize {
+        self.free_count
+    }
+
     /// Adds a new element, returning a given ID associated with it.
-    pub fn add(&mut self, node: T) -> ID {
-        let new_cycle_stamp;
-        let added_at_index;
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error.
+    pub fn add(&mut self, node: T) -> ID<T, K> {
+        match self.try_add(node) {
+            Ok(id) => id,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Errors
+    /// This function returns error of type `Expired` if the slot that would be reused has run out
+    /// of cycle stamps and the `ExhaustionPolicy` is `Error`. The slot is left as it is, so later
+    /// calls that reach it fail the same way. It returns error of type `NotCompatible` if the key
+    /// `K` cannot index any more elements. Either way, the element is given back in the error.
+    pub fn try_add(&mut self, node: T) -> Result<ID<T, K>, ReusableIndexAddError<T>> {
+        match self.next_id() {
+            Ok(id) => {
+                self.fill_slot(id, node);
+                Ok(id)
+            },
+            Err(error) => Err(ReusableIndexAddError::new(error, node)),
+        }
+    }
 
-        if let Some(last_removed) = self.last_removed {
+    /// Adds a new element built by `build`, which receives the ID the element is going to have.
+    /// Useful for elements that store their own ID.
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error. `build` is not called in that case.
+    pub fn add_with<F>(&mut self, build: F) -> ID<T, K>
+    where F: FnOnce(ID<T, K>) -> T {
+        match self.next_id() {
+            Ok(id) => {
+                self.fill_slot(id, build(id));
+                id
+            },
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Returns an entry for the slot the next element is going to take, which knows the ID that
+    /// element is going to have. The slot is not claimed until the entry is filled.
+    ///
+    /// ```
+    ///     use bugeutils::list::{ReusableIndexVec, ID};
+    ///
+    ///     struct Entity {
+    ///         own_id: ID<Entity>,
+    ///     }
+    ///
+    ///     let mut entities = ReusableIndexVec::new();
+    ///
+    ///     let entry = entities.vacant_entry();
+    ///     let own_id = entry.id();
+    ///     let id = entry.insert(Entity { own_id });
+    ///
+    ///     assert_eq!(entities.get(id).unwrap().own_id, id);
+    /// ```
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error.
+    pub fn vacant_entry(&mut self) -> ReusableIndexVacantEntry<'_, T, K> {
+        match self.next_id() {
+            Ok(id) => ReusableIndexVacantEntry::new(self, id),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Returns the ID the next added element is going to have, without taking the slot. Exhausted
+    // slots at the head of the free list are retired along the way, unless the policy is `Error`.
+    fn next_id(&mut self) -> ListResult<ID<T, K>> {
+        self.settle_reservations();
+
+        while let Some(last_removed) = self.last_removed {
             // A node has been removed before, let's use his place in his memory.
             debug_assert!(last_removed < self.vector.len(), "[LOGIC ERROR] Last removed index is out of bounds!");
 
-            added_at_index = last_removed; // It will add the new one here
+            // Under `ExhaustionPolicy::Error`, the slot stays at the head of the free list, so
+            // that every call reports it.
+            match free_list_head(&self.vector[last_removed], last_removed, self.exhaustion_policy)? {
+                (FreeListHead::Reuse(new_cycle_stamp), _) => return Ok(ID::from_untyped(K::new(new_cycle_stamp, last_removed))),
+                (FreeListHead::Retire(cycle_stamp), next_removed) => {
+                    // The slot has run out of stamps, it must never be used again.
+                    self.vector[last_removed] = ReusableIndexNode::Retired(cycle_stamp);
+                    self.set_last_removed(next_removed);
+                    self.free_count -= 1;
+                    self.retired_count += 1;
+                },
+            }
+        }
+
+        // A brand new node is going to be created.
+        let added_at_index = self.vector.len();
+        check_fresh_index::<K>(added_at_index)?;
 
-            let node_state = &self.vector[last_removed];
+        Ok(ID::from_untyped(K::new(self.fresh_stamp, added_at_index)))
+    }
 
-            match node_state {
-                ReusableIndexNode::Removed(cycle_stamp) => {
-                    new_cycle_stamp = cycle_stamp.wrapping_add(1);
-                    self.vector[last_removed] = ReusableIndexNode::Exists(new_cycle_stamp, node);
-                    self.last_removed = None;
-                },
-                ReusableIndexNode::RemovedAndNext(cycle_stamp, next_removed) => {
-                    new_cycle_stamp = cycle_stamp.wrapping_add(1);
-                    let next_removed = *next_removed; // Make a copy of the next_removed value, as it will be replaced...
-                    self.vector[last_removed] = ReusableIndexNode::Exists(new_cycle_stamp, node); // ...on this line
-                    self.last_removed = Some(next_removed);
+    #[inline]
+    // Returns the stamp a removed slot with `cycle_stamp` gets when reused under the policy of the
+    // vector.
+    fn reused_stamp(&self, cycle_stamp: K::Stamp) -> Option<K::Stamp> {
+        reused_stamp::<K>(cycle_stamp, self.exhaustion_policy)
+    }
+
+    // Every change of the free list head goes through here, so that `reserve_id` sees it.
+    #[inline]
+    fn set_last_removed(&mut self, last_removed: Option<Index>) {
+        self.last_removed = last_removed;
+        *self.reserve_head.get_mut() = last_removed.unwrap_or(NO_INDEX);
+        if last_removed.is_none() {
+            self.free_tail = None;
+        }
+    }
+
+    // Puts the removed slot at `index` on the free list, where the policy wants it.
+    fn push_free(&mut self, index: Index) {
+        let (previous, next) = match self.free_list_policy {
+            FreeListPolicy::Lifo => (None, self.last_removed),
+            FreeListPolicy::Fifo => (self.free_tail, None),
+            FreeListPolicy::LowestIndexFirst => self.sorted_free_position(index),
+        };
+
+        self.link_free(index, next);
+        match previous {
+            Some(previous) => self.link_free(previous, Some(index)),
+            None => self.set_last_removed(Some(index)),
+        }
+        if next.is_none() {
+            self.free_tail = Some(index);
+        }
+        self.free_count += 1;
+    }
+
+    // Finds the slots on the free list between which `index` goes to keep it sorted.
+    fn sorted_free_position(&self, index: Index) -> (Option<Index>, Option<Index>) {
+        match (self.last_removed, self.free_tail) {
+            (Some(head), _) if index < head => return (None, Some(head)),
+            (_, Some(tail)) if index > tail => return (Some(tail), None),
+            _ => (),
+        }
+
+        // The closest removed slot below `index` comes right before it on the free list. It is
+        // looked for among the slots that hold no element, skipping the retired and reserved ones.
+        let mut below = index;
+        let previous = loop {
+            match prev_vacant(self.occupied.words(), below) {
+                Some(vacant) => match self.vector[vacant] {
+                    ReusableIndexNode::Removed(_) | ReusableIndexNode::RemovedAndNext(_, _) => break Some(vacant),
+                    _ => below = vacant,
                 },
-                // This should never actually execute. If it does, it is a bug.
-                ReusableIndexNode::Exists(_, _) => panic!("[LOGIC ERROR] Node at {} should not exist", last_removed),
+                None => break None,
             }
+        };
+
+        let next = match previous {
+            Some(previous) => match self.vector[previous] {
+                ReusableIndexNode::RemovedAndNext(_, next_removed) => Some(next_removed.to_index()),
+                _ => None,
+            },
+            None => self.last_removed,
+        };
+        (previous, next)
+    }
+
+    // Makes the removed node at `index` point to `next` on the free list.
+    #[inline]
+    fn link_free(&mut self, index: Index, next: Option<Index>) {
+        let cycle_stamp = self.vector[index].cycle_stamp();
+        self.vector[index] = match next {
+            Some(next) => ReusableIndexNode::RemovedAndNext(cycle_stamp, K::Index::from_index(next)),
+            None => ReusableIndexNode::Removed(cycle_stamp),
+        };
+    }
+
+    // Puts `node` on the slot of `id`, which must have been just returned by `next_id`.
+    fn fill_slot(&mut self, id: ID<T, K>, node: T) {
+        let index = id.index();
+
+        if self.last_removed == Some(index) {
+            // Make a copy of the next_removed value, as the node will be replaced.
+            let next_removed = match self.vector[index] {
+                ReusableIndexNode::RemovedAndNext(_, next_removed) => Some(next_removed.to_index()),
+                _ => None,
+            };
+            self.set_last_removed(next_removed);
+            self.vector[index] = ReusableIndexNode::Exists(id.stamp(), node);
+            self.occupied.set(index);
+            self.free_count -= 1;
         } else {
-            // Creating a brand new node.
-            new_cycle_stamp = 0;
-            self.vector.push(ReusableIndexNode::Exists(new_cycle_stamp, node));
-            added_at_index = self.vector.len() - 1;
+            debug_assert_eq!(index, self.vector.len(), "[LOGIC ERROR] Slot {} is not the next one", index);
+            self.vector.push(ReusableIndexNode::Exists(id.stamp(), node));
+            self.occupied.set(index);
         }
 
-        ID(new_cycle_stamp, added_at_index)
+        self.live_count += 1;
+        self.debug_check_integrity();
     }
 
     /// Removes the element associated with the given ID.
     ///
     /// # Errors
-    /// This function returns error of type `NotFound` if the element has never existed, or was removed.
-    pub fn remove(&mut self, id: ID) -> ListResult<()> {
-        let (requested_cycle_stamp, index) = (id.0, id.1);
+    /// This function returns error of type `NotFound` if the element has never existed, and of type
+    /// `Expired` if it was removed, even if its slot holds another element now.
+    #[inline]
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn remove(&mut self, id: ID<T, K>) -> ListResult<()> {
+        self.take(id).map(|_| ())
+    }
 
-        if index < self.vector.len() {
-            if let ReusableIndexNode::Exists(cycle_stamp, _) = self.vector[index] {
-                if requested_cycle_stamp == cycle_stamp {
-                    if let Some(last_removed) = self.last_removed {
-                        self.vector[index] = ReusableIndexNode::RemovedAndNext(cycle_stamp, last_removed);
-                    } else {
-                        self.vector[index] = ReusableIndexNode::Removed(cycle_stamp);
-                    }
+    /// Removes the element associated with the given ID, giving it back.
+    ///
+    /// # Errors
+    /// This function returns error of type `NotFound` if the element has never existed, and of type
+    /// `Expired` if it was removed, even if its slot holds another element now.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn take(&mut self, id: ID<T, K>) -> ListResult<T> {
+        self.check_id(id)?;
+        Ok(self.remove_by_index(id.index()).expect("[LOGIC ERROR] Checked node should exist"))
+    }
 
-                    self.last_removed = Some(index);
+    /// Replaces the element associated with the given ID by `node`, giving back the old one. The
+    /// ID stays the same.
+    ///
+    /// # Errors
+    /// This function returns error of type `NotFound` if the element has never existed, and of type
+    /// `Expired` if it was removed, even if its slot holds another element now.
+    pub fn replace(&mut self, id: ID<T, K>, node: T) -> ListResult<T> {
+        Ok(mem::replace(self.try_get_mut(id)?, node))
+    }
 
-                    return Ok(())
+    /// Keeps only the elements for which `keep` returns `true`, removing the others.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn retain<F>(&mut self, mut keep: F)
+    where F: FnMut(ID<T, K>, &mut T) -> bool {
+        let mut next = next_occupied(self.occupied.words(), 0, self.vector.len());
+        while let Some(index) = next {
+            if let ReusableIndexNode::Exists(cycle_stamp, ref mut node) = self.vector[index] {
+                if !keep(ID::from_untyped(K::new(cycle_stamp, index)), node) {
+                    self.remove_by_index(index);
                 }
             }
+            next = next_occupied(self.occupied.words(), index + 1, self.vector.len());
+        }
+    }
+
+    #[inline]
+    /// Returns a cursor on the first existing element, which can remove or replace elements while
+    /// walking over them.
+    pub fn cursor_mut(&mut self) -> ReusableIndexCursorMut<'_, T, K> {
+        ReusableIndexCursorMut::new(self)
+    }
+
+    // Removes whatever exists at `index`, regardless of its cycle stamp.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    fn remove_by_index(&mut self, index: Index) -> Option<T> {
+        let cycle_stamp = match self.vector.get(index) {
+            Some(ReusableIndexNode::Exists(cycle_stamp, _)) => *cycle_stamp,
+            _ => return None,
+        };
+
+        self.live_count -= 1;
+        #[cfg(all(feature = "debug-removals", debug_assertions))]
+        self.record_removal(index);
+
+        let node = match self.release_slot(index, cycle_stamp) {
+            ReusableIndexNode::Exists(_, node) => node,
+            _ => unreachable!("[LOGIC ERROR] Node at {} should exist", index),
+        };
+        self.debug_check_integrity();
+        Some(node)
+    }
+
+    // Puts the slot at `index` back on the free list, with `cycle_stamp` as its last stamp, or
+    // retires it if it cannot be reused. Returns the node that was there.
+    fn release_slot(&mut self, index: Index, cycle_stamp: K::Stamp) -> ReusableIndexNode<T, K> {
+        self.settle_reservations();
+        self.occupied.clear(index);
+
+        if self.exhaustion_policy == ExhaustionPolicy::Retire && cycle_stamp == K::MAX_STAMP {
+            // The slot cannot be reused without wrapping, so it does not go back to the free list.
+            self.retired_count += 1;
+            mem::replace(&mut self.vector[index], ReusableIndexNode::Retired(cycle_stamp))
+        } else {
+            let old_node = mem::replace(&mut self.vector[index], ReusableIndexNode::Removed(cycle_stamp));
+            self.push_free(index);
+            old_node
         }
+    }
 
-        Err(BugeError::new(BugeErrorType::NotFound, &format!("node with id {}::{} not found", requested_cycle_stamp, index)))
+    // Builds a vector out of its parts, checking that they make sense, as they may come from a
+    // saved vector that was corrupted. The counters are worked out from the nodes.
+    fn from_raw_parts(
+        vector: Vec<ReusableIndexNode<T, K>>,
+        last_removed: Option<Index>,
+        fresh_stamp: K::Stamp,
+        exhaustion_policy: ExhaustionPolicy,
+        free_list_policy: FreeListPolicy,
+    ) -> ListResult<Self> {
+        let layout = match FreeListLayout::of(&vector, last_removed, fresh_stamp, free_list_policy) {
+            Ok(layout) => layout,
+            Err(desc) => return Err(BugeError::new(BugeErrorType::InvalidParameter, &desc)),
+        };
+        let occupied = Occupancy::from_slots(vector.iter().map(|node| matches!(node, ReusableIndexNode::Exists(_, _))));
+        let reserved = vector.iter().enumerate()
+            .filter(|(_, node)| matches!(node, ReusableIndexNode::Reserved(_)))
+            .map(|(index, _)| index)
+            .collect();
+
+        Ok(Self {
+            vector,
+            last_removed,
+            free_tail: layout.free_tail,
+            free_list_policy,
+            live_count: layout.live_count,
+            free_count: layout.free_count,
+            fresh_stamp,
+            exhaustion_policy,
+            retired_count: layout.retired_count,
+            reserve_head: AtomicUsize::new(last_removed.unwrap_or(NO_INDEX)),
+            reserve_extra: AtomicUsize::new(0),
+            reserved,
+            occupied,
+            removed_at: RemovalLog::default(),
+        })
     }
 
-    // Not used
-    //fn remove_by_index(&mut self, index: Index) -> ListResult<()> {
-    //    if index < self.vector.len() {
-    //        if let ReusableIndexNode::Exists(cycle_stamp, _) = self.vector[index] {
-    //            if let Some(last_removed) = self.last_removed {
-    //                self.vector[index] = ReusableIndexNode::RemovedAndNext(cycle_stamp, last_removed);
-    //            } else {
-    //                self.vector[index] = ReusableIndexNode::Removed(cycle_stamp);
-    //            }
+    // Threads the free list through the removed nodes at `indices`, the first one being the next
+    // to be reused. Every removed node must be given, or it is lost from the free list.
+    fn rebuild_free_list<I>(&mut self, indices: I)
+    where I: DoubleEndedIterator<Item = Index> {
+        let mut last_removed = None;
+        let mut free_tail = None;
+        self.free_count = 0;
+
+        for index in indices.rev() {
+            free_tail = free_tail.or(Some(index));
+            let cycle_stamp = self.vector[index].cycle_stamp();
+
+            self.vector[index] = if let Some(last_removed) = last_removed {
+                ReusableIndexNode::RemovedAndNext(cycle_stamp, K::Index::from_index(last_removed))
+            } else {
+                ReusableIndexNode::Removed(cycle_stamp)
+            };
+            last_removed = Some(index);
+            self.free_count += 1;
+        }
+
+        self.set_last_removed(last_removed);
+        self.free_tail = free_tail;
+    }
 
-    //            self.last_removed = Some(index);
+    // Returns the indices on the free list, in the order they would be reused.
+    fn free_list_indices(&self) -> Vec<Index> {
+        let mut indices = Vec::with_capacity(self.free_count);
+        let mut next_removed = self.last_removed;
+
+        while let Some(index) = next_removed {
+            indices.push(index);
+            next_removed = match self.vector[index] {
+                ReusableIndexNode::RemovedAndNext(_, next) => Some(next.to_index()),
+                _ => None,
+            };
+        }
 
-    //            Ok(())
-    //        } else {
-    //            Err(BugeError::new(BugeErrorType::NotFound, &format!("node with index {} does not exist. It cannot be removed", index)))
-    //        }
-    //    } else {
-    //        Err(BugeError::new(BugeErrorType::NotFound, &format!("index {} is out of bounds. It cannot be removed", index)))
-    //    }
-    //}
+        indices
+    }
 
-    fn get_by_index(&self, index: Index) -> Option<(CycleStamp, &T)> {
+    fn get_by_index(&self, index: Index) -> Option<(K::Stamp, &T)> {
         if index < self.vector.len() {
             if let ReusableIndexNode::Exists(cycle_stamp, ref node) = self.vector[index] {
                 Some((cycle_stamp, node))
@@ -190,7 +778,7 @@ impl<T> ReusableIndexVec<T> {
         }
     }
 
-    fn get_by_index_mut(&mut self, index: Index) -> Option<(CycleStamp, &mut T)> {
+    fn get_by_index_mut(&mut self, index: Index) -> Option<(K::Stamp, &mut T)> {
         if index < self.vector.len() {
             if let ReusableIndexNode::Exists(cycle_stamp, ref mut node) = self.vector[index] {
                 Some((cycle_stamp, node))
@@ -202,11 +790,17 @@ impl<T> ReusableIndexVec<T> {
         }
     }
 
+    #[inline]
+    // Checks that `id` refers to an element in the vector, with the errors of `check_status`.
+    fn check_id(&self, id: ID<T, K>) -> ListResult<()> {
+        check_status(id, self.status(id), &self.removed_at)
+    }
+
     /// Returns a reference to the element associated with the given ID.
     ///
     /// Returns `None` if the element does not exist.
-    pub fn get(&mut self, id: ID) -> Option<&T> {
-        let ID(cycle_stamp, index) = id;
+    pub fn get(&self, id: ID<T, K>) -> Option<&T> {
+        let (cycle_stamp, index) = (id.stamp(), id.index());
         let (found_cycle_stamp, node) = self.get_by_index(index)?;
 
         // If it is REALLY the same
@@ -217,11 +811,37 @@ impl<T> ReusableIndexVec<T> {
         }
     }
 
+    #[inline]
+    /// Returns true if the given ID is associated with an element of the vector.
+    pub fn contains(&self, id: ID<T, K>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns error of type `NotFound` if the element has never existed, and of type
+    /// `Expired` if it was removed, even if its slot holds another element now.
+    pub fn try_get(&self, id: ID<T, K>) -> ListResult<&T> {
+        self.check_id(id)?;
+        Ok(self.get(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns error of type `NotFound` if the element has never existed, and of type
+    /// `Expired` if it was removed, even if its slot holds another element now.
+    pub fn try_get_mut(&mut self, id: ID<T, K>) -> ListResult<&mut T> {
+        self.check_id(id)?;
+        Ok(self.get_mut(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
     /// Returns a mutable reference to the element associated with the given ID.
     ///
     /// Returns `None` if the element does not exist.
-    pub fn get_mut(&mut self, id: ID) -> Option<&mut T> {
-        let ID(cycle_stamp, index) = id;
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut T> {
+        let (cycle_stamp, index) = (id.stamp(), id.index());
         let (found_cycle_stamp, node) = self.get_by_index_mut(index)?;
 
         // If it is REALLY the same
@@ -232,46 +852,254 @@ impl<T> ReusableIndexVec<T> {
         }
     }
 
+    /// Returns mutable references to the elements associated with each of the given IDs, at once.
+    ///
+    /// # Errors
+    /// Returns an `InvalidParameter` error if the same ID is given twice, a `NotFound` error if an
+    /// ID has never referred to any element, and an `Expired` error if its element was removed.
+    ///
+    /// # Examples
+    /// ```
+    /// use bugeutils::list::ReusableIndexVec;
+    ///
+    /// let mut health = ReusableIndexVec::new();
+    /// let (a, b) = (health.add(10), health.add(3));
+    ///
+    /// let [giver, taker] = health.get_many_mut([a, b]).unwrap();
+    /// *giver -= 5;
+    /// *taker += 5;
+    /// assert_eq!(health.get(a), Some(&5));
+    /// assert_eq!(health.get(b), Some(&8));
+    ///
+    /// assert!(health.get_many_mut([a, a]).is_err());
+    /// ```
+    pub fn get_many_mut<const N: usize>(&mut self, ids: [ID<T, K>; N]) -> ListResult<[&mut T; N]> {
+        for (position, id) in ids.iter().enumerate() {
+            self.check_id(*id)?;
+            if ids[..position].iter().any(|other| other.index() == id.index()) {
+                return Err(BugeError::new(BugeErrorType::InvalidParameter, &format!("node with id {} is requested more than once", id)));
+            }
+        }
+
+        // Hand out the slots in index order, splitting the rest of the vector off each time.
+        let mut order: [usize; N] = [0; N];
+        for (position, slot) in order.iter_mut().enumerate() {
+            *slot = position;
+        }
+        order.sort_unstable_by_key(|position| ids[*position].index());
+
+        let mut nodes: [Option<&mut T>; N] = [(); N].map(|_| None);
+        let mut rest = &mut self.vector[..];
+        let mut offset = 0;
+        for position in order.iter() {
+            let index = ids[*position].index();
+            let (head, tail) = mem::take(&mut rest).split_at_mut(index - offset + 1);
+            rest = tail;
+            offset = index + 1;
+
+            if let Some(ReusableIndexNode::Exists(_, node)) = head.last_mut() {
+                nodes[*position] = Some(node);
+            }
+        }
+
+        Ok(nodes.map(|node| node.expect("[LOGIC ERROR] Checked node should exist")))
+    }
+
     /// Returns a slice to a list of nodes.
     ///
     /// This is a slice to a very raw slice. It contains every element, including the removed ones.
     #[inline]
-    pub fn as_slice(&self) -> &[ReusableIndexNode<T>] {
+    pub fn as_slice(&self) -> &[ReusableIndexNode<T, K>] {
         self.vector.as_slice()
     }
 
     #[inline]
     /// Returns an iterator on the list of existing elements.
-    pub fn iter<'vec>(&'vec self) -> ReusableIndexIterator<'vec, T> {
-        ReusableIndexIterator {
-            slice: self.vector.as_slice(),
-            length: self.vector.len(),
-            index: 0,
-        }
+    pub fn iter(&self) -> ReusableIndexIterator<'_, T, K> {
+        ReusableIndexIterator::new(self)
+    }
+
+    #[inline]
+    /// Returns an iterator of mutable references on the list of existing elements.
+    pub fn iter_mut(&mut self) -> ReusableIndexIteratorMut<'_, T, K> {
+        ReusableIndexIteratorMut::new(self)
+    }
+
+    #[inline]
+    /// Returns an iterator on the list of existing elements, yielding each one with its ID.
+    pub fn iter_with_ids(&self) -> ReusableIndexIdIterator<'_, T, K> {
+        ReusableIndexIdIterator::new(self)
+    }
+
+    #[inline]
+    /// Returns an iterator of mutable references on the list of existing elements, yielding each
+    /// one with its ID.
+    pub fn iter_mut_with_ids(&mut self) -> ReusableIndexIdIteratorMut<'_, T, K> {
+        ReusableIndexIdIteratorMut::new(self)
+    }
+
+    #[inline]
+    /// Removes every element, returning them through an iterator. The slots are kept, so the IDs
+    /// of the removed elements do not become valid again.
+    pub fn drain(&mut self) -> ReusableIndexDrain<'_, T, K> {
+        ReusableIndexDrain::new(self)
     }
 } // End of impl ReusableIndexVec
 
-#[derive(Debug, Clone, Copy)]
-pub struct ReusableIndexIterator<'vec, T> {
-    slice: &'vec [ReusableIndexNode<T>],
-    length: usize,
-    index: usize,
+// What adding an element does with the slot at the head of a free list.
+pub(super) enum FreeListHead<S> {
+    // The slot takes the element, with the given stamp.
+    Reuse(S),
+    // The slot has run out of stamps and is retired, keeping the given stamp, the last it had.
+    Retire(S),
 }
 
-impl<'vec, T> Iterator for ReusableIndexIterator<'vec, T> {
-    type Item = &'vec T;
+// Returns the stamp a removed slot with `cycle_stamp` gets when reused, or `None` if the slot
+// has run out of stamps and `policy` does not allow wrapping.
+pub(super) fn reused_stamp<K: Key>(cycle_stamp: K::Stamp, policy: ExhaustionPolicy) -> Option<K::Stamp> {
+    match K::next_stamp(cycle_stamp) {
+        Some(new_cycle_stamp) => Some(new_cycle_stamp),
+        None if policy == ExhaustionPolicy::Wrap => Some(K::Stamp::ZERO),
+        None => None,
+    }
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.index < self.length {
-                if let ReusableIndexNode::Exists(_, ref item) = self.slice[self.index] {
-                    self.index += 1;
-                    break Some(item);
-                }
-                self.index += 1;
-            } else {
-                break None;
-            }
+// Tells what adding an element does with `node`, the removed slot at `index` at the head of a
+// free list, along with the slot that comes after it on the free list. Under
+// `ExhaustionPolicy::Error`, a slot that has run out of stamps gives the error `try_add` returns.
+pub(super) fn free_list_head<X, K: Key>(node: &ReusableIndexNode<X, K>, index: Index, policy: ExhaustionPolicy)
+    -> ListResult<(FreeListHead<K::Stamp>, Option<Index>)> {
+    let (cycle_stamp, next_removed) = match *node {
+        ReusableIndexNode::Removed(cycle_stamp) => (cycle_stamp, None),
+        ReusableIndexNode::RemovedAndNext(cycle_stamp, next_removed) => (cycle_stamp, Some(next_removed.to_index())),
+        // This should never actually execute. If it does, it is a bug.
+        _ => panic!("[LOGIC ERROR] Node at {} should not be on the free list", index),
+    };
+
+    let head = match reused_stamp::<K>(cycle_stamp, policy) {
+        Some(new_cycle_stamp) => FreeListHead::Reuse(new_cycle_stamp),
+        None if policy == ExhaustionPolicy::Error => {
+            return Err(BugeError::new(BugeErrorType::Expired, &format!("slot {} has run out of cycle stamps", index)));
+        },
+        None => FreeListHead::Retire(cycle_stamp),
+    };
+    Ok((head, next_removed))
+}
+
+// Checks that the key `K` can index a new slot at `index`, past the last one.
+pub(super) fn check_fresh_index<K: Key>(index: Index) -> ListResult<()> {
+    if index > K::MAX_INDEX {
+        return Err(BugeError::new(BugeErrorType::NotCompatible, &format!("cannot add more than {} elements with this key", K::MAX_INDEX + 1)));
+    }
+    Ok(())
+}
+
+// Tells what an ID with `stamp` refers to, given `node`, the slot at its index, out of `len`.
+// Reservations that have not been settled are not seen.
+pub(super) fn slot_status<X, K: Key>(node: Option<&ReusableIndexNode<X, K>>, stamp: K::Stamp, len: usize) -> ReusableIndexStatus<K::Stamp> {
+    match node {
+        Some(ReusableIndexNode::Exists(cycle_stamp, _)) if *cycle_stamp == stamp => ReusableIndexStatus::Live,
+        Some(ReusableIndexNode::Reserved(cycle_stamp)) if *cycle_stamp == stamp => ReusableIndexStatus::Reserved,
+        Some(ReusableIndexNode::Exists(cycle_stamp, _)) |
+        Some(ReusableIndexNode::Reserved(cycle_stamp)) => ReusableIndexStatus::Reused { current_stamp: *cycle_stamp },
+        Some(node) => ReusableIndexStatus::Removed { stamp: node.cycle_stamp() },
+        None => ReusableIndexStatus::OutOfBounds { len },
+    }
+}
+
+// Checks that an ID with the given status refers to an element. The error is `NotFound` if the
+// slot has never held anything with this ID, and `Expired` if its element was removed, naming
+// the stamp of the element living in the slot now, if any, and where the slot was freed, if
+// `removals` knows it.
+pub(super) fn check_status<T, K: Key>(id: ID<T, K>, status: ReusableIndexStatus<K::Stamp>, removals: &RemovalLog) -> ListResult<()> {
+    let index = id.index();
+    match status {
+        ReusableIndexStatus::Live => Ok(()),
+        ReusableIndexStatus::Reserved => {
+            Err(BugeError::new(BugeErrorType::NotFound, &format!("node with id {} has not been flushed yet", id)))
+        },
+        ReusableIndexStatus::Removed { .. } => {
+            Err(BugeError::new(BugeErrorType::Expired, &format!("node with id {} has expired, slot {} is empty{}", id, index, removals.note(index))))
+        },
+        ReusableIndexStatus::Reused { current_stamp } => {
+            Err(BugeError::new(BugeErrorType::Expired, &format!("node with id {} has expired, slot {} now holds stamp {}{}", id, index, current_stamp, removals.note(index))))
+        },
+        ReusableIndexStatus::OutOfBounds { .. } => {
+            Err(BugeError::new(BugeErrorType::NotFound, &format!("node with id {} not found", id)))
+        },
+    }
+}
+
+// Where each slot was last freed, remembered by debug builds with the `debug-removals` feature so
+// that the errors for expired IDs can tell it. Otherwise it remembers nothing.
+#[derive(Debug, Default)]
+pub(super) struct RemovalLog {
+    #[cfg(all(feature = "debug-removals", debug_assertions))]
+    removed_at: Vec<Option<&'static Location<'static>>>,
+}
+
+#[cfg(all(feature = "debug-removals", debug_assertions))]
+impl RemovalLog {
+    #[track_caller]
+    // Remembers that the slot at `index` is being freed by the caller.
+    pub fn record(&mut self, index: Index) {
+        if self.removed_at.len() <= index {
+            self.removed_at.resize(index + 1, None);
+        }
+        self.removed_at[index] = Some(Location::caller());
+    }
+
+    // Describes where the slot at `index` was last freed, for error messages.
+    pub fn note(&self, index: Index) -> String {
+        match self.removed_at.get(index) {
+            Some(Some(location)) => format!(", freed at {}", location),
+            _ => String::new(),
+        }
+    }
+
+    #[inline]
+    // Forgets the slots from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        self.removed_at.truncate(len);
+    }
+}
+
+#[cfg(not(all(feature = "debug-removals", debug_assertions)))]
+impl RemovalLog {
+    #[inline]
+    pub fn note(&self, _index: Index) -> String {
+        String::new()
+    }
+
+    #[inline]
+    pub fn truncate(&mut self, _len: usize) {}
+}
+
+impl<T, K: Key> Default for ReusableIndexVec<T, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+/// Indexing with an ID that is not associated with any element panics, naming the ID and why it
+/// is invalid. Use `get` where the element may be missing.
+impl<T, K: Key> ops::Index<ID<T, K>> for ReusableIndexVec<T, K> {
+    type Output = T;
+
+    fn index(&self, id: ID<T, K>) -> &T {
+        match self.try_get(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for ReusableIndexVec: {}", err),
+        }
+    }
+}
+
+impl<T, K: Key> ops::IndexMut<ID<T, K>> for ReusableIndexVec<T, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut T {
+        match self.try_get_mut(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for ReusableIndexVec: {}", err),
         }
     }
 }
@@ -279,6 +1107,7 @@ impl<'vec, T> Iterator for ReusableIndexIterator<'vec, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::list::{PackedID, WideID};
 
     #[test]
     fn size_test() {
@@ -287,7 +1116,317 @@ mod tests {
         // This sizes are expected on a 64-bit machine.
         assert_eq!(mem::size_of::<ReusableIndexNode<u32>>(), 16);
         assert_eq!(mem::size_of::<ReusableIndexNode<u64>>(), 16);
-        assert_eq!(mem::size_of::<ReusableIndexNode<u128>>(), 24);
+        // Newer compilers align `u128` to 16 bytes on x86_64, which pads the node to 32 bytes.
+        let u128_node_size = if mem::align_of::<u128>() == 16 { 32 } else { 24 };
+        assert_eq!(mem::size_of::<ReusableIndexNode<u128>>(), u128_node_size);
+
+        assert_eq!(mem::size_of::<ReusableIndexNode<u32, PackedID>>(), 8);
+        assert_eq!(mem::size_of::<ReusableIndexNode<u64, WideID>>(), 24);
+    }
+
+    #[test]
+    fn other_keys_test() {
+        let mut packed_vec = ReusableIndexVec::<u32, PackedID>::with_key();
+
+        let id_a = packed_vec.add(10);
+        let id_b = packed_vec.add(20);
+        packed_vec.remove(id_a).unwrap();
+        let id_c = packed_vec.add(30);
+
+        assert_eq!(id_c.index(), id_a.index());
+        assert_eq!(id_c.stamp(), 1);
+        assert_eq!(packed_vec.get(id_a), None);
+        assert_eq!(packed_vec.get(id_b), Some(&20));
+        assert_eq!(packed_vec.get(id_c), Some(&30));
+
+        let mut wide_vec = ReusableIndexVec::<u32, WideID>::with_key();
+
+        let id_a = wide_vec.add(10);
+        wide_vec.remove(id_a).unwrap();
+        let id_b = wide_vec.add(20);
+
+        assert_eq!(id_b.untyped(), WideID(1, 0));
+        assert_eq!(wide_vec.get(id_a), None);
+        assert_eq!(wide_vec.get(id_b), Some(&20));
+    }
+
+    #[test]
+    fn exhaustion_policy_test() {
+        type Vec12 = ReusableIndexVec<u32, PackedID>;
+
+        // Removes and adds on the first slot until its stamp is the last one.
+        fn exhaust(vec: &mut Vec12) -> ID<u32, PackedID> {
+            let mut id = vec.add(0);
+            while id.stamp() < PackedID::MAX_STAMP {
+                vec.remove(id).unwrap();
+                id = vec.add(0);
+            }
+            id
+        }
+
+        let mut wrapping_vec = Vec12::with_key();
+        let old_id = exhaust(&mut wrapping_vec);
+        wrapping_vec.remove(old_id).unwrap();
+        let new_id = wrapping_vec.add(1);
+        assert_eq!(new_id.stamp(), 0);
+        assert_eq!(new_id.index(), old_id.index());
+        assert_eq!(wrapping_vec.retired_slot_count(), 0);
+
+        let mut retiring_vec = Vec12::with_key();
+        retiring_vec.set_exhaustion_policy(ExhaustionPolicy::Retire);
+        let old_id = exhaust(&mut retiring_vec);
+        retiring_vec.remove(old_id).unwrap();
+        assert!(matches!(retiring_vec.as_slice()[0], ReusableIndexNode::Retired(_)));
+        let new_id = retiring_vec.add(1);
+        assert_ne!(new_id.index(), old_id.index());
+        assert_eq!(retiring_vec.retired_slot_count(), 1);
+        assert_eq!(retiring_vec.iter().collect::<Vec<_>>(), vec![&1]);
+
+        let mut erring_vec = Vec12::with_key();
+        erring_vec.set_exhaustion_policy(ExhaustionPolicy::Error);
+        let old_id = exhaust(&mut erring_vec);
+        erring_vec.remove(old_id).unwrap();
+        let err = erring_vec.try_add(1).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::Expired);
+        assert_eq!(err.into_value(), 1);
+        // The slot is not skipped, nor by later calls, nor by reservations.
+        assert_eq!(erring_vec.try_add(2).unwrap_err().into_parts().1, 2);
+        assert_eq!(erring_vec.try_reserve_id().unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(erring_vec.try_add(3).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(erring_vec.retired_slot_count(), 0);
+        assert_eq!(erring_vec.len(), 0);
+
+        // Until the policy lets it go.
+        erring_vec.set_exhaustion_policy(ExhaustionPolicy::Retire);
+        let new_id = erring_vec.try_add(4).unwrap();
+        assert_ne!(new_id.index(), old_id.index());
+        assert_eq!(erring_vec.retired_slot_count(), 1);
+    }
+
+    #[test]
+    fn free_list_policy_test() {
+        let reuse_order = |policy| {
+            let mut vec_tad: ReusableIndexVec<u32> = ReusableIndexVec::with_free_list_policy(policy);
+            let ids: Vec<_> = (0..6).map(|number| vec_tad.add(number)).collect();
+            for index in [3, 1, 5, 0, 4].iter() {
+                vec_tad.remove(ids[*index]).unwrap();
+            }
+            assert_eq!(vec_tad.free_slot_count(), 5);
+
+            let reused: Vec<_> = (0..5).map(|_| vec_tad.add(10).index()).collect();
+            assert_eq!(vec_tad.free_slot_count(), 0);
+            assert_eq!(vec_tad.add(10).index(), 6);
+            reused
+        };
+
+        assert_eq!(reuse_order(FreeListPolicy::Lifo), vec![4, 0, 5, 1, 3]);
+        assert_eq!(reuse_order(FreeListPolicy::Fifo), vec![3, 1, 5, 0, 4]);
+        assert_eq!(reuse_order(FreeListPolicy::LowestIndexFirst), vec![0, 1, 3, 4, 5]);
+
+        // Removing and adding in turns keeps the order.
+        let mut vec_tad: ReusableIndexVec<u32> = ReusableIndexVec::with_free_list_policy(FreeListPolicy::Fifo);
+        let ids: Vec<_> = (0..3).map(|number| vec_tad.add(number)).collect();
+        vec_tad.remove(ids[0]).unwrap();
+        vec_tad.remove(ids[1]).unwrap();
+        let id_a = vec_tad.add(10);
+        vec_tad.remove(ids[2]).unwrap();
+        vec_tad.remove(id_a).unwrap();
+        assert_eq!((0..3).map(|_| vec_tad.add(10).index()).collect::<Vec<_>>(), vec![1, 2, 0]);
+
+        let mut vec_tad = ReusableIndexVec::<u32, PackedID>::with_capacity_and_free_list_policy(4, FreeListPolicy::LowestIndexFirst);
+        assert_eq!(vec_tad.free_list_policy(), FreeListPolicy::LowestIndexFirst);
+        let ids: Vec<_> = (0..4).map(|number| vec_tad.add(number)).collect();
+        vec_tad.remove(ids[2]).unwrap();
+        vec_tad.remove(ids[3]).unwrap();
+        vec_tad.remove(ids[1]).unwrap();
+        let reserved: Vec<_> = (0..3).map(|_| vec_tad.reserve_id().index()).collect();
+        assert_eq!(reserved, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn take_and_replace_test() {
+        let mut vec_tad = ReusableIndexVec::<String>::new();
+
+        let id_a = vec_tad.add(String::from("String A"));
+        let id_b = vec_tad.add(String::from("String B"));
+
+        assert_eq!(vec_tad.replace(id_a, String::from("String C")).unwrap(), "String A");
+        assert_eq!(vec_tad.get(id_a), Some(&String::from("String C")));
+
+        assert_eq!(vec_tad.take(id_a).unwrap(), "String C");
+        assert_eq!(vec_tad.get(id_a), None);
+        assert!(vec_tad.take(id_a).is_err());
+        assert!(vec_tad.replace(id_a, String::from("String D")).is_err());
+
+        // The slot taken from goes back to the free list.
+        let id_e = vec_tad.add(String::from("String E"));
+        assert_eq!(id_e.index(), id_a.index());
+        assert_eq!(vec_tad.get(id_b), Some(&String::from("String B")));
+    }
+
+    #[test]
+    fn counters_test() {
+        let mut vec_tad = ReusableIndexVec::<u32>::new();
+
+        assert!(vec_tad.is_empty());
+
+        let ids: Vec<_> = (0..10).map(|i| vec_tad.add(i)).collect();
+        for id in &ids[..4] {
+            vec_tad.remove(*id).unwrap();
+        }
+
+        assert_eq!(vec_tad.len(), 6);
+        assert_eq!(vec_tad.slot_count(), 10);
+        assert_eq!(vec_tad.free_slot_count(), 4);
+        assert_eq!(vec_tad.iter().len(), 6);
+
+        vec_tad.add(10);
+        assert_eq!(vec_tad.len(), 7);
+        assert_eq!(vec_tad.free_slot_count(), 3);
+
+        vec_tad.drain().for_each(drop);
+        assert!(vec_tad.is_empty());
+        assert_eq!(vec_tad.slot_count(), 10);
+        assert_eq!(vec_tad.free_slot_count(), 10);
+    }
+
+    #[test]
+    fn retain_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..10).collect();
+        let id_3 = vec_tad.iter_with_ids().nth(3).unwrap().0;
+
+        vec_tad.retain(|id, node| {
+            *node += 100;
+            id != id_3 && *node % 2 == 0
+        });
+
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![100, 102, 104, 106, 108]);
+        assert_eq!(vec_tad.get(id_3), None);
+        assert_eq!(vec_tad.free_slot_count(), 5);
+
+        let reused: Vec<_> = (0..5).map(|i| vec_tad.add(i).index()).collect();
+        assert_eq!(reused, vec![9, 7, 5, 3, 1]);
+    }
+
+    #[test]
+    fn get_many_mut_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..5).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+
+        // The references come back in the order the IDs were given.
+        {
+            let [e, a, c] = vec_tad.get_many_mut([ids[4], ids[0], ids[2]]).unwrap();
+            assert_eq!((*e, *a, *c), (4, 0, 2));
+            mem::swap(e, a);
+            *c += 10;
+        }
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![4, 1, 12, 3, 0]);
+        assert_eq!(vec_tad.get_many_mut::<0>([]).unwrap().len(), 0);
+
+        let err = vec_tad.get_many_mut([ids[1], ids[3], ids[1]]).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::InvalidParameter);
+
+        vec_tad.remove(ids[3]).unwrap();
+        let err = vec_tad.get_many_mut([ids[1], ids[3]]).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::Expired);
+        let reused = vec_tad.add(30);
+        let err = vec_tad.get_many_mut([reused, ids[3]]).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::Expired);
+
+        let missing = UntypedID(0, 10).typed();
+        let err = vec_tad.get_many_mut([ids[1], missing]).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::NotFound);
+    }
+
+    #[test]
+    fn shared_lookup_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+
+        // Two lookups at once, through a shared reference.
+        let shared = &vec_tad;
+        assert_eq!((shared.get(ids[0]), shared.get(ids[2])), (Some(&0), Some(&2)));
+        assert!(shared.contains(ids[1]));
+        assert_eq!(shared[ids[1]], 1);
+
+        vec_tad[ids[1]] += 10;
+        assert_eq!(vec_tad[ids[1]], 11);
+
+        vec_tad.remove(ids[1]).unwrap();
+        assert!(!vec_tad.contains(ids[1]));
+        vec_tad.add(20);
+        assert!(!vec_tad.contains(ids[1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Expired, node with id 0::1 has expired, slot 1 is empty")]
+    fn index_stale_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let id = vec_tad.iter_with_ids().nth(1).unwrap().0;
+        vec_tad.remove(id).unwrap();
+        vec_tad[id] = 5;
+    }
+
+    #[test]
+    #[should_panic(expected = "NotFound, node with id 0::7 not found")]
+    fn index_missing_test() {
+        let vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let _ = vec_tad[UntypedID(0, 7).typed()];
+    }
+
+    #[test]
+    fn expired_errors_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        assert_eq!(*vec_tad.try_get(ids[1]).unwrap(), 1);
+        *vec_tad.try_get_mut(ids[1]).unwrap() += 10;
+        assert_eq!(vec_tad[ids[1]], 11);
+
+        vec_tad.remove(ids[1]).unwrap();
+        let err = vec_tad.try_get(ids[1]).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::Expired);
+        assert!(err.to_string().contains("slot 1 is empty"), "{}", err);
+        assert_eq!(vec_tad.remove(ids[1]).unwrap_err().error_type(), BugeErrorType::Expired);
+
+        // Once reused, the error names the stamp living in the slot.
+        let reused = vec_tad.add(20);
+        for err in [
+            vec_tad.try_get(ids[1]).map(|_| ()).unwrap_err(),
+            vec_tad.try_get_mut(ids[1]).map(|_| ()).unwrap_err(),
+            vec_tad.take(ids[1]).map(|_| ()).unwrap_err(),
+            vec_tad.replace(ids[1], 0).map(|_| ()).unwrap_err(),
+        ] {
+            assert_eq!(err.error_type(), BugeErrorType::Expired);
+            assert!(err.to_string().contains(&format!("slot 1 now holds stamp {}", reused.stamp())), "{}", err);
+        }
+
+        // An index that was never allocated is not found.
+        let missing = UntypedID(0, 3).typed();
+        assert_eq!(vec_tad.try_get(missing).unwrap_err().error_type(), BugeErrorType::NotFound);
+        assert_eq!(vec_tad.remove(missing).unwrap_err().error_type(), BugeErrorType::NotFound);
+
+        // A reserved ID is not found until it is flushed.
+        let reserved = vec_tad.reserve_id();
+        assert_eq!(vec_tad.try_get(reserved).unwrap_err().error_type(), BugeErrorType::NotFound);
+        vec_tad.add(30);
+        assert_eq!(vec_tad.try_get(reserved).unwrap_err().error_type(), BugeErrorType::NotFound);
+    }
+
+    #[test]
+    fn add_with_test() {
+        struct Entity {
+            own_id: ID<Entity>,
+        }
+
+        let mut vec_tad = ReusableIndexVec::new();
+
+        let id_a = vec_tad.add_with(|own_id| Entity { own_id });
+        vec_tad.remove(id_a).unwrap();
+        let id_b = vec_tad.add_with(|own_id| Entity { own_id });
+
+        assert_eq!(id_b.index(), id_a.index());
+        assert_eq!(vec_tad.get(id_b).unwrap().own_id, id_b);
+        assert_eq!(vec_tad.free_slot_count(), 0);
     }
 
     #[test]
@@ -305,6 +1444,8 @@ mod tests {
         vec_tad.add("String E");
 
         assert_eq!(vec_tad.iter().collect::<Vec<&Type>>().len(), 5);
+        assert_eq!(vec_tad.len(), 5);
+        assert!(!vec_tad.is_empty());
     }
 
     #[test]
@@ -352,7 +1493,7 @@ mod tests {
 
         assert_eq!(test_vec.len(), 3);
 
-        //assert_eq!(test_vec, vec![&"String A", &"String C", &"String E"]);
+        assert_eq!(test_vec, vec![&"String A", &"String C", &"String E"]);
 
         assert_eq!(vec_tad.get(id_b), None);
         assert_eq!(vec_tad.get(id_d), None);
@@ -374,7 +1515,7 @@ mod tests {
 
         let test_vec = vec_tad.iter().collect::<Vec<&Type>>();
 
-        //assert_eq!(test_vec, vec![&"String A", &"String G", &"String C", &"String F", &"String E", &"String H", &"String I"]);
+        assert_eq!(test_vec, vec![&"String A", &"String G", &"String C", &"String F", &"String E", &"String H", &"String I"]);
 
         // Now removing 3, adding 2, removing 4.
         vec_tad.remove(id_c).unwrap();
@@ -404,7 +1545,7 @@ mod tests {
 
         let test_vec = vec_tad.iter().collect::<Vec<&Type>>();
 
-        //assert_eq!(test_vec, vec![&"String K", &"String E"]);
+        assert_eq!(test_vec, vec![&"String K", &"String E"]);
 
         // Test error
         assert!(vec_tad.remove(id_a).is_err());
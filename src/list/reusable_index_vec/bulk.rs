@@ -0,0 +1,212 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
+use crate::list::{Index, Key, ListResult, ID};
+
+use super::{ReusableIndexNode, ReusableIndexVec};
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Adds every element of `nodes`, returning their IDs in the same order.
+    ///
+    /// The removed slots are filled first, then the vector grows once for the rest of the
+    /// elements, as far as the size hint of `nodes` tells.
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error, with the elements added so far left in.
+    ///
+    /// # Examples
+    /// ```
+    /// use bugeutils::list::ReusableIndexVec;
+    ///
+    /// let mut particles = ReusableIndexVec::new();
+    /// let ids = particles.add_many((0..10_000).map(|n| n as f32));
+    /// assert_eq!(particles.len(), 10_000);
+    ///
+    /// assert_eq!(particles.remove_many(&ids[..5_000]), 5_000);
+    /// assert_eq!(particles.len(), 5_000);
+    /// ```
+    pub fn add_many<I>(&mut self, nodes: I) -> Vec<ID<T, K>>
+    where I: IntoIterator<Item = T> {
+        self.settle_reservations();
+
+        let mut nodes = nodes.into_iter();
+        let mut ids = Vec::with_capacity(nodes.size_hint().0);
+
+        while self.last_removed.is_some() {
+            match nodes.next() {
+                Some(node) => ids.push(self.add(node)),
+                None => return ids,
+            }
+        }
+
+        // The rest of the elements go past the end, all with the fresh stamp, as far as the key
+        // can index them.
+        let fresh_stamp = self.fresh_stamp;
+        let start = self.vector.len();
+        let room = if start > K::MAX_INDEX { 0 } else { (K::MAX_INDEX - start).saturating_add(1) };
+        self.vector.extend(nodes.by_ref().take(room).map(|node| ReusableIndexNode::Exists(fresh_stamp, node)));
+
+        let end = self.vector.len();
+        for index in start..end {
+            self.occupied.set(index);
+            ids.push(ID::from_untyped(K::new(fresh_stamp, index)));
+        }
+        self.live_count += end - start;
+        self.debug_check_integrity();
+
+        if let Some(node) = nodes.next() {
+            // There is no room left for it, so this panics the same way adding one by one does.
+            self.add(node);
+        }
+        ids
+    }
+
+    /// Removes the elements associated with the given IDs, skipping the IDs that are not
+    /// associated with any element, or that were already given. Returns how many elements were
+    /// removed.
+    ///
+    /// Each slot goes on the free list as soon as it is emptied, so the slots are reused in the
+    /// same order as if they had been removed one by one. The elements are dropped once all of
+    /// them are out.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn remove_many(&mut self, ids: &[ID<T, K>]) -> usize {
+        self.settle_reservations();
+
+        let ids: Vec<_> = ids.iter().copied().filter(|id| self.check_id(*id).is_ok()).collect();
+        self.remove_checked(&ids)
+    }
+
+    /// Removes the elements associated with the given IDs, or none of them if any ID is invalid.
+    ///
+    /// # Errors
+    /// Every ID is checked before anything is removed. This function returns error of type
+    /// `InvalidParameter` if the same ID is given twice, and otherwise the error `remove` would
+    /// return for the first invalid ID.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn try_remove_many(&mut self, ids: &[ID<T, K>]) -> ListResult<()> {
+        self.settle_reservations();
+
+        for id in ids.iter() {
+            self.check_id(*id)?;
+        }
+
+        let mut indices: Vec<Index> = ids.iter().map(|id| id.index()).collect();
+        indices.sort_unstable();
+        if let Some(pair) = indices.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(BugeError::new(BugeErrorType::InvalidParameter, &format!("slot {} is given more than once", pair[0])));
+        }
+
+        self.remove_checked(ids);
+        Ok(())
+    }
+
+    // Removes the elements of the given IDs, which must be valid, except for repeated IDs, which
+    // are skipped. Returns how many elements were removed.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    fn remove_checked(&mut self, ids: &[ID<T, K>]) -> usize {
+        let mut removed = Vec::with_capacity(ids.len());
+
+        for id in ids.iter() {
+            let index = id.index();
+            let cycle_stamp = match self.vector[index] {
+                ReusableIndexNode::Exists(cycle_stamp, _) if cycle_stamp == id.stamp() => cycle_stamp,
+                _ => continue,
+            };
+
+            self.live_count -= 1;
+            #[cfg(all(feature = "debug-removals", debug_assertions))]
+            self.record_removal(index);
+            removed.push(self.release_slot(index, cycle_stamp));
+        }
+        self.debug_check_integrity();
+
+        // The elements are dropped once the vector is whole again.
+        removed.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::FreeListPolicy;
+
+    #[test]
+    fn add_many_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..4).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[1]).unwrap();
+        vec_tad.remove(ids[2]).unwrap();
+
+        let added = vec_tad.add_many(10..15);
+        assert_eq!(added.iter().map(|id| id.index()).collect::<Vec<_>>(), vec![2, 1, 4, 5, 6]);
+        assert_eq!(added.iter().map(|id| vec_tad[*id]).collect::<Vec<_>>(), vec![10, 11, 12, 13, 14]);
+        assert_eq!(vec_tad.len(), 7);
+        assert_eq!(vec_tad.free_slot_count(), 0);
+
+        assert!(vec_tad.add_many(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn remove_many_test() {
+        // Removing in bulk reuses the slots in the same order as removing one by one.
+        for policy in [FreeListPolicy::Lifo, FreeListPolicy::Fifo, FreeListPolicy::LowestIndexFirst].iter() {
+            let mut one_by_one: ReusableIndexVec<u32> = ReusableIndexVec::with_free_list_policy(*policy);
+            let ids = one_by_one.add_many(0..8);
+            one_by_one.remove(ids[6]).unwrap();
+            let mut bulk: ReusableIndexVec<u32> = ReusableIndexVec::with_free_list_policy(*policy);
+            bulk.add_many(0..8);
+            bulk.remove(ids[6]).unwrap();
+
+            let to_remove = [ids[3], ids[0], ids[3], ids[6], ids[5]];
+            for id in to_remove.iter() {
+                let _ = one_by_one.remove(*id);
+            }
+            assert_eq!(bulk.remove_many(&to_remove), 3);
+            assert_eq!(bulk.len(), 4);
+            assert_eq!(bulk.free_slot_count(), 4);
+
+            let reused: Vec<_> = (0..5).map(|_| bulk.add(0)).collect();
+            assert_eq!(reused, (0..5).map(|_| one_by_one.add(0)).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn try_remove_many_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..5).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[4]).unwrap();
+
+        // Nothing is removed when an ID is invalid or repeated.
+        let err = vec_tad.try_remove_many(&[ids[0], ids[4]]).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::Expired);
+        let err = vec_tad.try_remove_many(&[ids[0], ids[1], ids[0]]).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::InvalidParameter);
+        assert_eq!(vec_tad.len(), 4);
+
+        vec_tad.try_remove_many(&[ids[0], ids[2]]).unwrap();
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(vec_tad.free_slot_count(), 3);
+        assert_eq!(vec_tad.add(10).index(), 2);
+    }
+}
@@ -0,0 +1,277 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{Index, Key, Stamp, UntypedID, ID};
+
+use super::{ExhaustionPolicy, ReusableIndexNode, ReusableIndexVec};
+
+use std::marker::PhantomData;
+use std::mem;
+
+#[derive(Debug)]
+/// Maps the IDs a `ReusableIndexVec` had before `compact` to the IDs it has after.
+pub struct ReusableIndexRemap<T, K: Key = UntypedID> {
+    // Indexed by the old index. Holds the old cycle stamp and the new key of each element.
+    entries: Vec<Option<(K::Stamp, K)>>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, K: Key> ReusableIndexRemap<T, K> {
+    /// Returns the new ID of the element that had `old_id`, or `None` if `old_id` was already
+    /// invalid before the compaction.
+    pub fn get(&self, old_id: ID<T, K>) -> Option<ID<T, K>> {
+        match self.entries.get(old_id.index()) {
+            Some(Some((old_cycle_stamp, new_key))) if *old_cycle_stamp == old_id.stamp() => Some(ID::from_untyped(*new_key)),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the old and new IDs of every element, including the ones that did
+    /// not move.
+    pub fn iter(&self) -> impl Iterator<Item = (ID<T, K>, ID<T, K>)> + '_ {
+        self.entries.iter().enumerate().filter_map(|(old_index, entry)| {
+            entry.map(|(old_cycle_stamp, new_key)| {
+                (ID::from_untyped(K::new(old_cycle_stamp, old_index)), ID::from_untyped(new_key))
+            })
+        })
+    }
+}
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Moves every existing element to the front of the vector, keeping their order, and drops the
+    /// removed slots that end up at the back.
+    ///
+    /// Elements that move get a new ID, so every ID held elsewhere must be patched with the
+    /// returned `ReusableIndexRemap`. The new IDs never match an ID that has been given before, so
+    /// IDs that were already invalid stay invalid.
+    pub fn compact(&mut self) -> ReusableIndexRemap<T, K> {
+        self.settle_reservations();
+
+        let mut entries = Vec::new();
+        entries.resize_with(self.vector.len(), || None);
+
+        let mut target = 0;
+
+        for (index, entry) in entries.iter_mut().enumerate() {
+            let cycle_stamp = match self.vector[index] {
+                ReusableIndexNode::Exists(cycle_stamp, _) => cycle_stamp,
+                _ => continue,
+            };
+
+            // Every slot before `index` that has not been filled is free. Finds the first one that
+            // can still take a new stamp.
+            let new_cycle_stamp = loop {
+                if target == index {
+                    break cycle_stamp;
+                }
+
+                let target_cycle_stamp = match self.vector[target] {
+                    ReusableIndexNode::Retired(_) | ReusableIndexNode::Reserved(_) => None,
+                    ref node => Some(node.cycle_stamp()),
+                };
+
+                if let Some(target_cycle_stamp) = target_cycle_stamp {
+                    match self.reused_stamp(target_cycle_stamp) {
+                        Some(new_cycle_stamp) => break new_cycle_stamp,
+                        // Under `ExhaustionPolicy::Error` the slot is left on the free list, for
+                        // the next `try_add` to report it.
+                        None if self.exhaustion_policy == ExhaustionPolicy::Error => (),
+                        None => {
+                            self.vector[target] = ReusableIndexNode::Retired(target_cycle_stamp);
+                            self.retired_count += 1;
+                        },
+                    }
+                }
+
+                target += 1;
+            };
+
+            if target != index {
+                if let ReusableIndexNode::Exists(_, node) = mem::replace(&mut self.vector[index], ReusableIndexNode::Removed(cycle_stamp)) {
+                    self.vector[target] = ReusableIndexNode::Exists(new_cycle_stamp, node);
+                    self.occupied.clear(index);
+                    self.occupied.set(target);
+                }
+            }
+
+            *entry = Some((cycle_stamp, K::new(new_cycle_stamp, target)));
+            target += 1;
+        }
+
+        self.truncate_removed(target);
+
+        let removed_indices: Vec<Index> = (0..self.vector.len())
+            .filter(|&index| matches!(self.vector[index], ReusableIndexNode::Removed(_) | ReusableIndexNode::RemovedAndNext(_, _)))
+            .collect();
+        self.rebuild_free_list(removed_indices.into_iter());
+        self.debug_check_integrity();
+
+        ReusableIndexRemap {
+            entries,
+            marker: PhantomData,
+        }
+    }
+
+    /// Drops the removed slots at the back of the vector and releases the memory that is not
+    /// needed anymore. Unlike `compact`, no element moves, so every ID stays valid.
+    pub fn shrink_to_fit(&mut self) {
+        self.settle_reservations();
+
+        let free_list = self.free_list_indices();
+        let old_len = self.vector.len();
+
+        self.truncate_removed(0);
+
+        let new_len = self.vector.len();
+        if new_len < old_len {
+            self.rebuild_free_list(free_list.into_iter().filter(|&index| index < new_len).collect::<Vec<_>>().into_iter());
+        }
+
+        self.vector.shrink_to_fit();
+        self.debug_check_integrity();
+    }
+
+    // Drops the removed slots at the back of the vector, keeping at least `min_len` slots. Raises
+    // the stamp of brand new slots past the stamps of the dropped ones. The free list must be
+    // rebuilt afterwards.
+    fn truncate_removed(&mut self, min_len: usize) {
+        let mut new_len = self.vector.len();
+
+        while new_len > min_len {
+            let cycle_stamp = match self.vector[new_len - 1] {
+                ReusableIndexNode::Removed(cycle_stamp) |
+                ReusableIndexNode::RemovedAndNext(cycle_stamp, _) => cycle_stamp,
+                _ => break,
+            };
+
+            let next_cycle_stamp = match K::next_stamp(cycle_stamp) {
+                Some(next_cycle_stamp) => next_cycle_stamp,
+                None if self.exhaustion_policy == ExhaustionPolicy::Wrap => K::Stamp::ZERO,
+                // An exhausted slot must stay, or its stamps would be given again.
+                None => break,
+            };
+
+            if next_cycle_stamp > self.fresh_stamp {
+                self.fresh_stamp = next_cycle_stamp;
+            }
+
+            new_len -= 1;
+        }
+
+        self.vector.truncate(new_len);
+        self.occupied.truncate(new_len);
+        self.removed_at.truncate(new_len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType as BugeErrorType;
+    use crate::list::PackedID;
+
+    #[test]
+    fn compact_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..8).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+
+        for i in &[0, 2, 3, 6, 7] {
+            vec_tad.remove(ids[*i]).unwrap();
+        }
+
+        let remap = vec_tad.compact();
+
+        assert_eq!(vec_tad.slot_count(), 3);
+        assert_eq!(vec_tad.free_slot_count(), 0);
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![1, 4, 5]);
+
+        for (old_index, value) in &[(1, 1), (4, 4), (5, 5)] {
+            let new_id = remap.get(ids[*old_index]).unwrap();
+            assert_eq!(vec_tad.get(new_id), Some(value));
+        }
+        assert_eq!(remap.get(ids[0]), None);
+        assert_eq!(remap.iter().count(), 3);
+
+        // None of the old IDs is valid again, not even the removed ones.
+        for id in &ids {
+            assert_eq!(vec_tad.get(*id), None);
+        }
+
+        // Slots past the end start with stamps that were never given at those indices.
+        let id_8 = vec_tad.add(8);
+        assert_eq!(id_8.index(), 3);
+        assert_eq!(vec_tad.get(ids[3]), None);
+    }
+
+    #[test]
+    fn compact_exhausted_test() {
+        for policy in [ExhaustionPolicy::Error, ExhaustionPolicy::Retire].iter() {
+            let mut vec_tad: ReusableIndexVec<u32, PackedID> = ReusableIndexVec::with_key();
+            vec_tad.set_exhaustion_policy(*policy);
+            let mut id = vec_tad.add(0);
+            while id.stamp() < PackedID::MAX_STAMP {
+                vec_tad.remove(id).unwrap();
+                id = vec_tad.add(0);
+            }
+            let kept = vec_tad.add(1);
+            vec_tad.remove(id).unwrap();
+
+            // The exhausted slot takes no element either way.
+            let remap = vec_tad.compact();
+            assert_eq!(remap.get(kept), Some(kept));
+            if *policy == ExhaustionPolicy::Error {
+                // It stays on the free list, for `try_add` to report it.
+                assert_eq!(vec_tad.retired_slot_count(), 0);
+                assert_eq!(vec_tad.free_slot_count(), 1);
+                assert_eq!(vec_tad.try_add(2).unwrap_err().error_type(), BugeErrorType::Expired);
+            } else {
+                assert_eq!(vec_tad.retired_slot_count(), 1);
+                assert_eq!(vec_tad.add(2).index(), 2);
+            }
+        }
+    }
+
+    #[test]
+    fn shrink_to_fit_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..6).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+
+        vec_tad.remove(ids[4]).unwrap();
+        vec_tad.remove(ids[1]).unwrap();
+        vec_tad.remove(ids[5]).unwrap();
+
+        vec_tad.shrink_to_fit();
+
+        assert_eq!(vec_tad.slot_count(), 4);
+        assert_eq!(vec_tad.free_slot_count(), 1);
+        for i in &[0, 2, 3] {
+            assert_eq!(vec_tad.get(ids[*i]), Some(&(*i as u32)));
+        }
+
+        assert_eq!(vec_tad.add(6).index(), 1);
+        let id_7 = vec_tad.add(7);
+        assert_eq!(id_7.index(), 4);
+        assert_ne!(id_7, ids[4]);
+        assert_eq!(vec_tad.get(ids[4]), None);
+    }
+}
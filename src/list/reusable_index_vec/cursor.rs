@@ -0,0 +1,175 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::occupancy::next_occupied;
+use crate::list::{Index, Key, UntypedID, ID};
+
+use super::{ReusableIndexNode, ReusableIndexVec};
+
+use std::mem;
+
+#[derive(Debug)]
+/// A cursor that walks over the existing elements of a `ReusableIndexVec`, being able to remove
+/// or replace them along the way.
+///
+/// The cursor always points either to an existing element, or past the end of the vector.
+///
+/// ```
+///     use bugeutils::list::ReusableIndexVec;
+///
+///     let mut particles: ReusableIndexVec<i32> = vec![3, -1, 4, -1, 5].into_iter().collect();
+///
+///     let mut cursor = particles.cursor_mut();
+///     while let Some(particle) = cursor.current() {
+///         if *particle < 0 {
+///             cursor.remove_current();
+///         } else {
+///             *particle *= 10;
+///             cursor.move_next();
+///         }
+///     }
+///
+///     assert_eq!(particles.iter().copied().collect::<Vec<_>>(), vec![30, 40, 50]);
+/// ```
+pub struct ReusableIndexCursorMut<'vec, T, K: Key = UntypedID> {
+    vec: &'vec mut ReusableIndexVec<T, K>,
+    index: Index,
+}
+
+impl<'vec, T, K: Key> ReusableIndexCursorMut<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec mut ReusableIndexVec<T, K>) -> Self {
+        let mut cursor = Self { vec, index: 0 };
+        cursor.seek_existing();
+        cursor
+    }
+
+    // Moves forward until an existing element is found, if the current one does not exist.
+    fn seek_existing(&mut self) {
+        let len = self.vec.vector.len();
+        self.index = next_occupied(self.vec.occupied.words(), self.index, len).unwrap_or(len);
+    }
+
+    /// Returns the ID of the current element, or `None` if the cursor is past the end.
+    pub fn id(&self) -> Option<ID<T, K>> {
+        match self.vec.vector.get(self.index) {
+            Some(ReusableIndexNode::Exists(cycle_stamp, _)) => Some(ID::from_untyped(K::new(*cycle_stamp, self.index))),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the current element, or `None` if the cursor is past the end.
+    pub fn peek(&self) -> Option<&T> {
+        match self.vec.vector.get(self.index) {
+            Some(ReusableIndexNode::Exists(_, node)) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the current element, or `None` if the cursor is past the
+    /// end.
+    pub fn current(&mut self) -> Option<&mut T> {
+        match self.vec.vector.get_mut(self.index) {
+            Some(ReusableIndexNode::Exists(_, node)) => Some(node),
+            _ => None,
+        }
+    }
+
+    /// Moves the cursor to the next existing element.
+    pub fn move_next(&mut self) {
+        if self.index < self.vec.vector.len() {
+            self.index += 1;
+            self.seek_existing();
+        }
+    }
+
+    /// Removes the current element, giving it back, and moves the cursor to the next existing
+    /// element. Returns `None` if the cursor is past the end.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn remove_current(&mut self) -> Option<T> {
+        let node = self.vec.remove_by_index(self.index)?;
+        self.move_next();
+        Some(node)
+    }
+
+    /// Replaces the current element by `node`, giving back the old one. The ID stays the same,
+    /// and the cursor does not move. Returns `None`, dropping `node`, if the cursor is past the
+    /// end.
+    pub fn replace_current(&mut self, node: T) -> Option<T> {
+        self.current().map(|old_node| mem::replace(old_node, node))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..6).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[0]).unwrap();
+
+        let mut cursor = vec_tad.cursor_mut();
+        assert_eq!(cursor.id(), Some(ids[1]));
+        assert_eq!(cursor.peek(), Some(&1));
+
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.id(), Some(ids[2]));
+
+        assert_eq!(cursor.replace_current(20), Some(2));
+        assert_eq!(cursor.id(), Some(ids[2]));
+
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(5));
+        assert_eq!(cursor.id(), None);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.remove_current(), None);
+        assert_eq!(cursor.replace_current(0), None);
+
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![20, 3, 4]);
+        assert_eq!(vec_tad.get(ids[2]), Some(&20));
+        assert_eq!(vec_tad.free_slot_count(), 3);
+
+        // The freed slots are reused, last removed first.
+        assert_eq!(vec_tad.add(6).index(), 5);
+        assert_eq!(vec_tad.add(7).index(), 1);
+        assert_eq!(vec_tad.add(8).index(), 0);
+    }
+
+    #[test]
+    fn sparse_cursor_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..300).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        assert_eq!(vec_tad.remove_many(&ids[1..270]), 269);
+
+        // The cursor jumps over the removed run, across several words of the occupancy bits.
+        let mut cursor = vec_tad.cursor_mut();
+        assert_eq!(cursor.remove_current(), Some(0));
+        assert_eq!(cursor.id(), Some(ids[270]));
+        while cursor.remove_current().is_some() {}
+        assert!(vec_tad.is_empty());
+    }
+}
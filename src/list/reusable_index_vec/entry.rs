@@ -0,0 +1,87 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{Key, UntypedID, ID};
+
+use super::ReusableIndexVec;
+
+#[derive(Debug)]
+/// A slot of a `ReusableIndexVec` that is about to be filled, see `ReusableIndexVec::vacant_entry`.
+///
+/// Dropping the entry without inserting anything leaves its slot unclaimed, so the next element
+/// gets the same ID. Anything done while looking for the slot stays done, though: pending
+/// reservations are settled, and exhausted slots at the head of the free list may be retired.
+pub struct ReusableIndexVacantEntry<'vec, T, K: Key = UntypedID> {
+    vec: &'vec mut ReusableIndexVec<T, K>,
+    id: ID<T, K>,
+}
+
+impl<'vec, T, K: Key> ReusableIndexVacantEntry<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec mut ReusableIndexVec<T, K>, id: ID<T, K>) -> Self {
+        Self { vec, id }
+    }
+
+    #[inline]
+    /// Returns the ID the element is going to have once inserted.
+    pub fn id(&self) -> ID<T, K> {
+        self.id
+    }
+
+    /// Fills the slot with `node`, returning its ID.
+    pub fn insert(self, node: T) -> ID<T, K> {
+        self.vec.fill_slot(self.id, node);
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vacant_entry_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let id_1 = vec_tad.iter_with_ids().nth(1).unwrap().0;
+        vec_tad.remove(id_1).unwrap();
+
+        // An entry that is dropped does not take the slot.
+        let entry_id = vec_tad.vacant_entry().id();
+        assert_eq!(entry_id.index(), 1);
+        assert_eq!(vec_tad.get(entry_id), None);
+        assert_eq!(vec_tad.free_slot_count(), 1);
+
+        let entry = vec_tad.vacant_entry();
+        assert_eq!(entry.id(), entry_id);
+        assert_eq!(entry.insert(10), entry_id);
+        assert_eq!(vec_tad.get(entry_id), Some(&10));
+        assert_eq!(vec_tad.free_slot_count(), 0);
+
+        // With the free list empty, the entry is past the end.
+        let entry = vec_tad.vacant_entry();
+        assert_eq!(entry.id().index(), 3);
+        let id_3 = entry.insert(30);
+        assert_eq!(vec_tad.len(), 4);
+        assert_eq!(vec_tad.get(id_3), Some(&30));
+    }
+}
@@ -0,0 +1,187 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
+use crate::list::{Index, Key, KeyIndex, ListResult};
+
+use super::{FreeListPolicy, ReusableIndexNode, ReusableIndexVec};
+
+// What walking the nodes and the free list of a vector found, when nothing is wrong with them.
+pub(super) struct FreeListLayout {
+    pub live_count: usize,
+    pub free_count: usize,
+    pub retired_count: usize,
+    pub free_tail: Option<Index>,
+}
+
+impl FreeListLayout {
+    // Walks the nodes, and the free list from `last_removed`, which must go through every
+    // removed node exactly once. Describes the first problem found, if any.
+    pub fn of<T, K: Key>(
+        vector: &[ReusableIndexNode<T, K>],
+        last_removed: Option<Index>,
+        fresh_stamp: K::Stamp,
+        free_list_policy: FreeListPolicy,
+    ) -> Result<Self, String> {
+        if vector.len() > K::MAX_INDEX.saturating_add(1) {
+            return Err(format!("{} slots cannot be indexed by this key", vector.len()));
+        }
+        if fresh_stamp > K::MAX_STAMP || vector.iter().any(|node| node.cycle_stamp() > K::MAX_STAMP) {
+            return Err(String::from("cycle stamp is too large for this key"));
+        }
+
+        let mut layout = FreeListLayout { live_count: 0, free_count: 0, retired_count: 0, free_tail: None };
+        for node in vector.iter() {
+            match node {
+                ReusableIndexNode::Exists(_, _) => layout.live_count += 1,
+                ReusableIndexNode::Retired(_) => layout.retired_count += 1,
+                _ => (),
+            }
+        }
+
+        let mut visited = vec![false; vector.len()];
+        let mut next_removed = last_removed;
+        while let Some(index) = next_removed {
+            if index >= vector.len() {
+                return Err(format!("free list points to index {}, which is out of bounds", index));
+            }
+            if visited[index] {
+                return Err(format!("free list has a cycle at index {}", index));
+            }
+            if free_list_policy == FreeListPolicy::LowestIndexFirst && matches!(layout.free_tail, Some(tail) if tail > index) {
+                return Err(format!("free list is not sorted at index {}", index));
+            }
+            visited[index] = true;
+            layout.free_count += 1;
+            layout.free_tail = Some(index);
+
+            next_removed = match vector[index] {
+                ReusableIndexNode::Removed(_) => None,
+                ReusableIndexNode::RemovedAndNext(_, next) => Some(next.to_index()),
+                _ => return Err(format!("free list points to index {}, which is not removed", index)),
+            };
+        }
+
+        let unreachable = vector.iter().zip(visited.iter()).position(|(node, visited)| {
+            !visited && matches!(node, ReusableIndexNode::Removed(_) | ReusableIndexNode::RemovedAndNext(_, _))
+        });
+        if let Some(index) = unreachable {
+            return Err(format!("removed node at index {} is not on the free list", index));
+        }
+
+        Ok(layout)
+    }
+}
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Walks the vector and its free list, checking that they are consistent.
+    ///
+    /// This is meant for debugging: a vector only gets inconsistent through a bug in this crate.
+    /// With the `debug-invariants` feature, the check is run after every change to the vector, and
+    /// a failure panics.
+    ///
+    /// # Errors
+    /// Returns an `UnexpectedError` describing the first problem found: a cycle in the free list,
+    /// a link out of bounds or to a node that is not removed, a removed node that cannot be reached
+    /// from the free list, or a counter that does not match the nodes.
+    pub fn check_integrity(&self) -> ListResult<()> {
+        let unexpected = |desc: &str| BugeError::new(BugeErrorType::UnexpectedError, desc);
+
+        let layout = FreeListLayout::of(&self.vector, self.last_removed, self.fresh_stamp, self.free_list_policy)
+            .map_err(|desc| unexpected(&desc))?;
+
+        if layout.live_count != self.live_count {
+            return Err(unexpected(&format!("{} elements are counted, but {} exist", self.live_count, layout.live_count)));
+        }
+        if layout.free_count != self.free_count {
+            return Err(unexpected(&format!("{} free slots are counted, but {} are on the free list", self.free_count, layout.free_count)));
+        }
+        if layout.retired_count != self.retired_count {
+            return Err(unexpected(&format!("{} retired slots are counted, but {} are retired", self.retired_count, layout.retired_count)));
+        }
+        if layout.free_tail != self.free_tail {
+            return Err(unexpected(&format!("free list ends at {:?}, but its tail is {:?}", layout.free_tail, self.free_tail)));
+        }
+
+        let misplaced = self.vector.iter().enumerate()
+            .position(|(index, node)| matches!(node, ReusableIndexNode::Exists(_, _)) != self.occupied.get(index));
+        if let Some(index) = misplaced {
+            return Err(unexpected(&format!("slot {} is not marked as it should in the occupancy bits", index)));
+        }
+
+        let reserved_count = self.vector.iter().filter(|node| matches!(node, ReusableIndexNode::Reserved(_))).count();
+        let all_reserved = self.reserved.iter().all(|index| matches!(self.vector.get(*index), Some(ReusableIndexNode::Reserved(_))));
+        if reserved_count != self.reserved.len() || !all_reserved {
+            return Err(unexpected(&format!("{} reserved slots are tracked, but {} are reserved", self.reserved.len(), reserved_count)));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    // Panics if the vector is inconsistent, with the `debug-invariants` feature. Called at the end
+    // of everything that changes the vector.
+    pub(super) fn debug_check_integrity(&self) {
+        #[cfg(feature = "debug-invariants")]
+        {
+            if let Err(err) = self.check_integrity() {
+                panic!("[LOGIC ERROR] ReusableIndexVec is inconsistent: {}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A vector with the free list 2 -> 4 -> 1.
+    fn sample_vec() -> ReusableIndexVec<u32> {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..6).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for index in [1, 4, 2].iter() {
+            vec_tad.remove(ids[*index]).unwrap();
+        }
+        vec_tad
+    }
+
+    #[test]
+    fn check_integrity_test() {
+        assert!(sample_vec().check_integrity().is_ok());
+
+        let broken = |change: fn(&mut ReusableIndexVec<u32>)| {
+            let mut vec_tad = sample_vec();
+            change(&mut vec_tad);
+            let err = vec_tad.check_integrity().unwrap_err();
+            assert_eq!(err.error_type(), BugeErrorType::UnexpectedError);
+            err.to_string()
+        };
+
+        assert!(broken(|v| v.vector[1] = ReusableIndexNode::RemovedAndNext(0, 2)).contains("cycle at index 2"));
+        assert!(broken(|v| v.vector[1] = ReusableIndexNode::RemovedAndNext(0, 9)).contains("index 9, which is out of bounds"));
+        assert!(broken(|v| v.vector[4] = ReusableIndexNode::RemovedAndNext(0, 3)).contains("index 3, which is not removed"));
+        assert!(broken(|v| v.vector[4] = ReusableIndexNode::Removed(0)).contains("removed node at index 1 is not on the free list"));
+        assert!(broken(|v| v.live_count += 1).contains("4 elements are counted, but 3 exist"));
+        assert!(broken(|v| v.free_tail = Some(4)).contains("free list ends at Some(1), but its tail is Some(4)"));
+    }
+}
@@ -0,0 +1,613 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::occupancy::{next_occupied, prev_occupied, Occupancy};
+use crate::list::{Index, Key, UntypedID, ID};
+
+use super::{ReusableIndexNode, ReusableIndexVec};
+
+use std::iter::{FromIterator, FusedIterator};
+use std::{slice, vec};
+
+#[derive(Debug, Clone, Copy)]
+/// An iterator over references to the existing elements of a `ReusableIndexVec`.
+pub struct ReusableIndexIterator<'vec, T, K: Key = UntypedID> {
+    slice: &'vec [ReusableIndexNode<T, K>],
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> ReusableIndexIterator<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec ReusableIndexVec<T, K>) -> Self {
+        Self {
+            slice: vec.vector.as_slice(),
+            occupied: vec.occupied.words(),
+            front: 0,
+            back: vec.vector.len(),
+            remaining: vec.live_count,
+        }
+    }
+
+    fn next_entry(&mut self) -> Option<(ID<T, K>, &'vec T)> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        self.front = index + 1;
+
+        self.entry_at(index)
+    }
+
+    fn next_back_entry(&mut self) -> Option<(ID<T, K>, &'vec T)> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        self.back = index;
+
+        self.entry_at(index)
+    }
+
+    #[inline]
+    fn entry_at(&mut self, index: Index) -> Option<(ID<T, K>, &'vec T)> {
+        match self.slice[index] {
+            ReusableIndexNode::Exists(cycle_stamp, ref item) => {
+                self.remaining -= 1;
+                Some((ID::from_untyped(K::new(cycle_stamp, index)), item))
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ReusableIndexIterator<'vec, T, K> {
+    type Item = &'vec T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|(_, item)| item)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ReusableIndexIterator<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_back_entry().map(|(_, item)| item)
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ReusableIndexIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ReusableIndexIterator<'vec, T, K> {}
+
+#[derive(Debug, Clone, Copy)]
+/// An iterator over the IDs of the existing elements of a `ReusableIndexVec`, together with
+/// references to them.
+pub struct ReusableIndexIdIterator<'vec, T, K: Key = UntypedID> {
+    inner: ReusableIndexIterator<'vec, T, K>,
+}
+
+impl<'vec, T, K: Key> ReusableIndexIdIterator<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec ReusableIndexVec<T, K>) -> Self {
+        Self {
+            inner: ReusableIndexIterator::new(vec),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ReusableIndexIdIterator<'vec, T, K> {
+    type Item = (ID<T, K>, &'vec T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_entry()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ReusableIndexIdIterator<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back_entry()
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ReusableIndexIdIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ReusableIndexIdIterator<'vec, T, K> {}
+
+#[derive(Debug)]
+/// An iterator over mutable references to the existing elements of a `ReusableIndexVec`.
+pub struct ReusableIndexIteratorMut<'vec, T, K: Key = UntypedID> {
+    // Holds the nodes from `front` up to, not including, `back`.
+    inner: slice::IterMut<'vec, ReusableIndexNode<T, K>>,
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> ReusableIndexIteratorMut<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec mut ReusableIndexVec<T, K>) -> Self {
+        Self {
+            remaining: vec.live_count,
+            front: 0,
+            back: vec.vector.len(),
+            occupied: vec.occupied.words(),
+            inner: vec.vector.iter_mut(),
+        }
+    }
+
+    fn next_entry(&mut self) -> Option<(ID<T, K>, &'vec mut T)> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        let node = self.inner.nth(index - self.front);
+        self.front = index + 1;
+
+        Self::entry_at(&mut self.remaining, index, node)
+    }
+
+    fn next_back_entry(&mut self) -> Option<(ID<T, K>, &'vec mut T)> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        let node = self.inner.nth_back(self.back - 1 - index);
+        self.back = index;
+
+        Self::entry_at(&mut self.remaining, index, node)
+    }
+
+    #[inline]
+    fn entry_at(remaining: &mut usize, index: Index, node: Option<&'vec mut ReusableIndexNode<T, K>>)
+        -> Option<(ID<T, K>, &'vec mut T)> {
+        match node {
+            Some(ReusableIndexNode::Exists(cycle_stamp, ref mut item)) => {
+                *remaining -= 1;
+                Some((ID::from_untyped(K::new(*cycle_stamp, index)), item))
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ReusableIndexIteratorMut<'vec, T, K> {
+    type Item = &'vec mut T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|(_, item)| item)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ReusableIndexIteratorMut<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.next_back_entry().map(|(_, item)| item)
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ReusableIndexIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ReusableIndexIteratorMut<'vec, T, K> {}
+
+#[derive(Debug)]
+/// An iterator over the IDs of the existing elements of a `ReusableIndexVec`, together with
+/// mutable references to them.
+pub struct ReusableIndexIdIteratorMut<'vec, T, K: Key = UntypedID> {
+    inner: ReusableIndexIteratorMut<'vec, T, K>,
+}
+
+impl<'vec, T, K: Key> ReusableIndexIdIteratorMut<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec mut ReusableIndexVec<T, K>) -> Self {
+        Self {
+            inner: ReusableIndexIteratorMut::new(vec),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ReusableIndexIdIteratorMut<'vec, T, K> {
+    type Item = (ID<T, K>, &'vec mut T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_entry()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ReusableIndexIdIteratorMut<'vec, T, K> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back_entry()
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ReusableIndexIdIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ReusableIndexIdIteratorMut<'vec, T, K> {}
+
+#[derive(Debug)]
+/// An iterator that moves the existing elements out of a `ReusableIndexVec`.
+pub struct ReusableIndexIntoIterator<T, K: Key = UntypedID> {
+    // Holds the nodes from `front` up to, not including, `back`.
+    inner: vec::IntoIter<ReusableIndexNode<T, K>>,
+    occupied: Occupancy,
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<T, K: Key> ReusableIndexIntoIterator<T, K> {
+    #[inline]
+    fn item_at(&mut self, index: Index, node: Option<ReusableIndexNode<T, K>>) -> Option<T> {
+        match node {
+            Some(ReusableIndexNode::Exists(_, item)) => {
+                self.remaining -= 1;
+                Some(item)
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<T, K: Key> Iterator for ReusableIndexIntoIterator<T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied.words(), self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        let node = self.inner.nth(index - self.front);
+        self.front = index + 1;
+
+        self.item_at(index, node)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T, K: Key> DoubleEndedIterator for ReusableIndexIntoIterator<T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied.words(), self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        let node = self.inner.nth_back(self.back - 1 - index);
+        self.back = index;
+
+        self.item_at(index, node)
+    }
+}
+
+impl<T, K: Key> ExactSizeIterator for ReusableIndexIntoIterator<T, K> {}
+
+impl<T, K: Key> FusedIterator for ReusableIndexIntoIterator<T, K> {}
+
+#[derive(Debug)]
+/// An iterator that removes the existing elements of a `ReusableIndexVec`, giving them back.
+///
+/// The slots are not dropped, they go back to the free list, so the IDs of the removed elements
+/// stay invalid. If the iterator is dropped before the end, the remaining elements are removed
+/// anyway.
+pub struct ReusableIndexDrain<'vec, T, K: Key = UntypedID> {
+    vec: &'vec mut ReusableIndexVec<T, K>,
+    front: Index,
+    back: Index,
+}
+
+impl<'vec, T, K: Key> ReusableIndexDrain<'vec, T, K> {
+    #[inline]
+    pub(super) fn new(vec: &'vec mut ReusableIndexVec<T, K>) -> Self {
+        Self {
+            front: 0,
+            back: vec.vector.len(),
+            vec,
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for ReusableIndexDrain<'vec, T, K> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match next_occupied(self.vec.occupied.words(), self.front, self.back) {
+            Some(index) => {
+                self.front = index + 1;
+                self.vec.remove_by_index(index)
+            },
+            None => {
+                self.front = self.back;
+                None
+            },
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.vec.live_count, Some(self.vec.live_count))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for ReusableIndexDrain<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        match prev_occupied(self.vec.occupied.words(), self.front, self.back) {
+            Some(index) => {
+                self.back = index;
+                self.vec.remove_by_index(index)
+            },
+            None => {
+                self.back = self.front;
+                None
+            },
+        }
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for ReusableIndexDrain<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for ReusableIndexDrain<'vec, T, K> {}
+
+impl<'vec, T, K: Key> Drop for ReusableIndexDrain<'vec, T, K> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
+impl<T, K: Key> IntoIterator for ReusableIndexVec<T, K> {
+    type Item = T;
+    type IntoIter = ReusableIndexIntoIterator<T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        ReusableIndexIntoIterator {
+            remaining: self.live_count,
+            front: 0,
+            back: self.vector.len(),
+            occupied: self.occupied,
+            inner: self.vector.into_iter(),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> IntoIterator for &'vec ReusableIndexVec<T, K> {
+    type Item = &'vec T;
+    type IntoIter = ReusableIndexIterator<'vec, T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'vec, T, K: Key> IntoIterator for &'vec mut ReusableIndexVec<T, K> {
+    type Item = &'vec mut T;
+    type IntoIter = ReusableIndexIteratorMut<'vec, T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, K: Key> FromIterator<T> for ReusableIndexVec<T, K> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut vec = Self::with_capacity_and_key(iter.size_hint().0);
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T, K: Key> Extend<T> for ReusableIndexVec<T, K> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for node in iter {
+            self.add(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A vector with holes: [A, -, C, -, E]
+    fn vector_with_holes() -> (ReusableIndexVec<String>, Vec<ID<String>>) {
+        let mut vec_tad = ReusableIndexVec::new();
+
+        let ids: Vec<_> = ["A", "B", "C", "D", "E"].iter().map(|s| vec_tad.add(String::from(*s))).collect();
+
+        vec_tad.remove(ids[1]).unwrap();
+        vec_tad.remove(ids[3]).unwrap();
+
+        (vec_tad, ids)
+    }
+
+    #[test]
+    fn double_ended_test() {
+        let (vec_tad, _) = vector_with_holes();
+
+        let mut iter = vec_tad.iter();
+        assert_eq!(iter.size_hint(), (3, Some(3)));
+        assert_eq!(iter.next_back().map(String::as_str), Some("E"));
+        assert_eq!(iter.next().map(String::as_str), Some("A"));
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+        assert_eq!(iter.next_back().map(String::as_str), Some("C"));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+
+        let reversed: Vec<_> = vec_tad.iter().rev().map(String::as_str).collect();
+        assert_eq!(reversed, vec!["E", "C", "A"]);
+    }
+
+    #[test]
+    fn iter_with_ids_test() {
+        let (mut vec_tad, ids) = vector_with_holes();
+
+        let with_ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, s)| (id, s.as_str())).collect();
+        assert_eq!(with_ids, vec![(ids[0], "A"), (ids[2], "C"), (ids[4], "E")]);
+
+        for (id, s) in vec_tad.iter_mut_with_ids() {
+            s.push_str(&id.index().to_string());
+        }
+        for s in &mut vec_tad {
+            s.push('!');
+        }
+
+        assert_eq!(vec_tad.get(ids[2]).map(String::as_str), Some("C2!"));
+
+        let removed: Vec<_> = vec_tad.iter_with_ids().rev().map(|(id, _)| id).collect();
+        for id in removed {
+            vec_tad.remove(id).unwrap();
+        }
+        assert_eq!(vec_tad.iter().next(), None);
+    }
+
+    #[test]
+    fn into_iter_test() {
+        let (vec_tad, _) = vector_with_holes();
+
+        let borrowed: Vec<_> = (&vec_tad).into_iter().cloned().collect();
+        let owned: Vec<_> = vec_tad.into_iter().collect();
+
+        assert_eq!(owned, vec!["A", "C", "E"]);
+        assert_eq!(borrowed, owned);
+    }
+
+    #[test]
+    fn drain_test() {
+        let (mut vec_tad, ids) = vector_with_holes();
+
+        let mut drain = vec_tad.drain();
+        assert_eq!(drain.size_hint(), (3, Some(3)));
+        assert_eq!(drain.next().as_deref(), Some("A"));
+        drop(drain); // The rest is removed anyway
+
+        assert_eq!(vec_tad.iter().next(), None);
+        for id in ids {
+            assert_eq!(vec_tad.get(id), None);
+        }
+
+        // The slots are reused, with new stamps.
+        let id_f = vec_tad.add(String::from("F"));
+        assert!(id_f.index() < 5);
+        assert_ne!(id_f.stamp(), 0);
+    }
+
+    #[test]
+    fn sparse_iteration_test() {
+        // Long runs of removed slots, crossing several words of the occupancy bits.
+        let mut vec_tad: ReusableIndexVec<usize> = (0..1000).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for id in ids.iter().filter(|id| ![0, 63, 64, 500, 999].contains(&id.index())) {
+            vec_tad.remove(*id).unwrap();
+        }
+
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), vec![0, 63, 64, 500, 999]);
+        assert_eq!(vec_tad.iter().rev().copied().collect::<Vec<_>>(), vec![999, 500, 64, 63, 0]);
+
+        let mut iter = vec_tad.iter_mut_with_ids();
+        assert_eq!(iter.next_back().map(|(id, _)| id), Some(ids[999]));
+        assert_eq!(iter.next().map(|(id, _)| id), Some(ids[0]));
+        assert_eq!(iter.next_back().map(|(id, _)| id), Some(ids[500]));
+        assert_eq!(iter.next().map(|(id, _)| id), Some(ids[63]));
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+        for (_, item) in iter {
+            *item += 1;
+        }
+        assert_eq!(vec_tad[ids[64]], 65);
+
+        let mut drain = vec_tad.drain();
+        assert_eq!(drain.next_back(), Some(999));
+        assert_eq!(drain.next(), Some(0));
+        drop(drain);
+        assert!(vec_tad.is_empty());
+        assert!(vec_tad.check_integrity().is_ok());
+
+        let mut vec_tad: ReusableIndexVec<usize> = (0..200).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for id in ids.iter().filter(|id| id.index() % 70 != 0) {
+            vec_tad.remove(*id).unwrap();
+        }
+        let mut owned = vec_tad.into_iter();
+        assert_eq!(owned.next_back(), Some(140));
+        assert_eq!(owned.collect::<Vec<_>>(), vec![0, 70]);
+    }
+
+    #[test]
+    fn from_iter_and_extend_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..4).collect();
+        vec_tad.extend(4..8);
+
+        assert_eq!(vec_tad.iter().copied().collect::<Vec<_>>(), (0..8).collect::<Vec<_>>());
+    }
+}
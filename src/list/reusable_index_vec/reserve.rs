@@ -0,0 +1,316 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use std::sync::atomic::Ordering;
+
+use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
+use crate::list::{Index, Key, KeyIndex, ListResult, ID};
+
+use super::{ExhaustionPolicy, ReusableIndexNode, ReusableIndexVec};
+
+// Marks an empty free list in `reserve_head`.
+pub(super) const NO_INDEX: Index = Index::MAX;
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Reserves an ID for an element that is going to be added later, with `flush_reserved`.
+    ///
+    /// This only needs a shared reference, so IDs can be reserved from many threads while the
+    /// vector is being iterated, for instance. The ID is taken from the free list, like `add`
+    /// would, or from past the end of the vector once the free list runs out. Until flushed, a
+    /// reserved ID is not present: `get` returns `None` for it. The counters (`len`,
+    /// `slot_count`, `free_slot_count`, ...) do not account for reservations until the vector
+    /// is next borrowed mutably.
+    ///
+    /// Reservations made since the vector was last borrowed mutably are not saved by serialization
+    /// or snapshots.
+    ///
+    /// # Panics
+    /// Panics whenever `try_reserve_id` would return an error.
+    ///
+    /// # Examples
+    /// ```
+    /// use bugeutils::list::ReusableIndexVec;
+    ///
+    /// let mut vec: ReusableIndexVec<u32> = (0..3).collect();
+    /// let reserved: Vec<_> = vec.iter().map(|_| vec.reserve_id()).collect();
+    /// assert_eq!(vec.get(reserved[0]), None);
+    ///
+    /// vec.flush_reserved(|id| Some(id.index() as u32 * 10));
+    /// assert_eq!(vec.get(reserved[0]), Some(&30));
+    /// assert_eq!(vec.len(), 6);
+    /// ```
+    pub fn reserve_id(&self) -> ID<T, K> {
+        match self.try_reserve_id() {
+            Ok(id) => id,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Reserves an ID for an element that is going to be added later, see `reserve_id`.
+    ///
+    /// # Errors
+    /// This function returns error of type `Expired` if the slot that would be reserved has run
+    /// out of cycle stamps and the `ExhaustionPolicy` is `Error`. The slot is left as it is, so
+    /// later calls that reach it fail the same way. It returns error of type `NotCompatible` if
+    /// the key `K` cannot index any more elements.
+    pub fn try_reserve_id(&self) -> ListResult<ID<T, K>> {
+        // The vector itself cannot change while it is borrowed, only the reservation counters
+        // do, so there is nothing else to synchronize with.
+        let mut head = self.reserve_head.load(Ordering::Relaxed);
+        while head != NO_INDEX {
+            let (cycle_stamp, next_removed) = match self.vector[head] {
+                ReusableIndexNode::Removed(cycle_stamp) => (cycle_stamp, NO_INDEX),
+                ReusableIndexNode::RemovedAndNext(cycle_stamp, next_removed) => (cycle_stamp, next_removed.to_index()),
+                _ => panic!("[LOGIC ERROR] Node at {} should not be on the free list", head),
+            };
+
+            let new_cycle_stamp = self.reused_stamp(cycle_stamp);
+            if new_cycle_stamp.is_none() && self.exhaustion_policy == ExhaustionPolicy::Error {
+                // The slot stays at the head of the free list, so that every call reports it.
+                return Err(BugeError::new(BugeErrorType::Expired, &format!("slot {} has run out of cycle stamps", head)));
+            }
+
+            match self.reserve_head.compare_exchange_weak(head, next_removed, Ordering::Relaxed, Ordering::Relaxed) {
+                // A slot that has run out of stamps is skipped, it is retired when settled.
+                Ok(_) => match new_cycle_stamp {
+                    Some(new_cycle_stamp) => return Ok(ID::from_untyped(K::new(new_cycle_stamp, head))),
+                    None => head = next_removed,
+                },
+                Err(current_head) => head = current_head,
+            }
+        }
+
+        // The free list is used up, reserve past the end.
+        let len = self.vector.len();
+        match self.reserve_extra.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |extra| {
+            if len + extra <= K::MAX_INDEX { Some(extra + 1) } else { None }
+        }) {
+            Ok(extra) => Ok(ID::from_untyped(K::new(self.fresh_stamp, len + extra))),
+            Err(_) => Err(BugeError::new(BugeErrorType::NotCompatible, &format!("cannot reserve more than {} elements with this key", K::MAX_INDEX.saturating_add(1)))),
+        }
+    }
+
+    /// Fills the slots reserved with `reserve_id`, in the order they were reserved.
+    ///
+    /// `fill` is called with every reserved ID. If it returns `Some` the element is added with
+    /// that ID, otherwise the slot is released and the ID stays invalid.
+    pub fn flush_reserved<F>(&mut self, mut fill: F)
+    where F: FnMut(ID<T, K>) -> Option<T> {
+        self.settle_reservations();
+
+        while let Some(index) = self.reserved.pop_front() {
+            let cycle_stamp = match self.vector[index] {
+                ReusableIndexNode::Reserved(cycle_stamp) => cycle_stamp,
+                _ => unreachable!("[LOGIC ERROR] Node at {} should be reserved", index),
+            };
+
+            match fill(ID::from_untyped(K::new(cycle_stamp, index))) {
+                Some(node) => {
+                    self.vector[index] = ReusableIndexNode::Exists(cycle_stamp, node);
+                    self.occupied.set(index);
+                    self.live_count += 1;
+                },
+                None => {
+                    self.release_slot(index, cycle_stamp);
+                },
+            }
+        }
+
+        self.debug_check_integrity();
+    }
+
+    /// Returns the number of reserved IDs that have not been flushed yet.
+    pub fn reserved_count(&self) -> usize {
+        self.reserved.len() + self.pending_free_indices().count() + self.reserve_extra.load(Ordering::Relaxed)
+    }
+
+    // Returns true if `id` has been reserved through a shared reference, and the reservation has
+    // not been settled yet.
+    pub(super) fn is_pending_reservation(&self, id: ID<T, K>) -> bool {
+        let len = self.vector.len();
+        if id.index() >= len {
+            id.index() - len < self.reserve_extra.load(Ordering::Relaxed) && id.stamp() == self.fresh_stamp
+        } else {
+            self.reused_stamp(self.vector[id.index()].cycle_stamp()) == Some(id.stamp())
+                && self.pending_free_indices().any(|index| index == id.index())
+        }
+    }
+
+    // Iterates over the free list slots that have been reserved, but not settled yet.
+    fn pending_free_indices(&self) -> impl Iterator<Item = Index> + '_ {
+        let head = self.reserve_head.load(Ordering::Relaxed);
+        let mut index = self.last_removed.filter(|index| *index != head);
+        std::iter::from_fn(move || {
+            let current = index?;
+            index = match self.vector[current] {
+                ReusableIndexNode::RemovedAndNext(_, next_removed) => Some(next_removed.to_index()),
+                _ => None,
+            }.filter(|index| *index != head);
+            Some(current)
+        })
+    }
+
+    // Turns the reservations made through a shared reference into `Reserved` nodes, so that the
+    // rest of the vector can be changed. Called before anything that touches the free list.
+    pub(super) fn settle_reservations(&mut self) {
+        let head = match *self.reserve_head.get_mut() {
+            NO_INDEX => None,
+            head => Some(head),
+        };
+
+        // Everything on the free list before the reservation head has been handed out.
+        while self.last_removed != head {
+            let index = self.last_removed.expect("[LOGIC ERROR] Reservation head should be on the free list");
+            let (cycle_stamp, next_removed) = match self.vector[index] {
+                ReusableIndexNode::Removed(cycle_stamp) => (cycle_stamp, None),
+                ReusableIndexNode::RemovedAndNext(cycle_stamp, next_removed) => (cycle_stamp, Some(next_removed.to_index())),
+                _ => panic!("[LOGIC ERROR] Node at {} should not be on the free list", index),
+            };
+
+            self.vector[index] = match self.reused_stamp(cycle_stamp) {
+                Some(new_cycle_stamp) => {
+                    self.reserved.push_back(index);
+                    ReusableIndexNode::Reserved(new_cycle_stamp)
+                },
+                None => {
+                    self.retired_count += 1;
+                    ReusableIndexNode::Retired(cycle_stamp)
+                },
+            };
+            self.set_last_removed(next_removed);
+            self.free_count -= 1;
+        }
+
+        let extra = std::mem::replace(self.reserve_extra.get_mut(), 0);
+        for _ in 0..extra {
+            self.reserved.push_back(self.vector.len());
+            self.vector.push(ReusableIndexNode::Reserved(self.fresh_stamp));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::PackedID;
+
+    #[test]
+    fn reserve_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..4).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[1]).unwrap();
+        vec_tad.remove(ids[2]).unwrap();
+
+        // The free list is used first, most recently removed slot first, then the end.
+        let reserved: Vec<_> = (0..3).map(|_| vec_tad.reserve_id()).collect();
+        assert_eq!(reserved.iter().map(|id| id.index()).collect::<Vec<_>>(), vec![2, 1, 4]);
+        assert_eq!(reserved[0].stamp(), ids[2].stamp() + 1);
+        assert_eq!(vec_tad.reserved_count(), 3);
+        for id in reserved.iter() {
+            assert_eq!(vec_tad.get(*id), None);
+        }
+
+        // Settling the reservations does not make them present.
+        let id_a = vec_tad.add(100);
+        assert_eq!(id_a.index(), 5);
+        assert_eq!(vec_tad.get(reserved[1]), None);
+        assert_eq!(vec_tad.len(), 3);
+        assert_eq!(vec_tad.reserved_count(), 3);
+
+        let mut filled = Vec::new();
+        vec_tad.flush_reserved(|id| {
+            filled.push(id);
+            if id == reserved[1] { None } else { Some(id.index() as u32) }
+        });
+        assert_eq!(filled, reserved);
+        assert_eq!(vec_tad.reserved_count(), 0);
+        assert_eq!(vec_tad.get(reserved[0]), Some(&2));
+        assert_eq!(vec_tad.get(reserved[1]), None);
+        assert_eq!(vec_tad.get(reserved[2]), Some(&4));
+        assert_eq!(vec_tad.len(), 5);
+        assert_eq!(vec_tad.free_slot_count(), 1);
+
+        // The released slot is reused with a newer stamp.
+        let id_b = vec_tad.add(200);
+        assert_eq!(id_b.index(), 1);
+        assert_ne!(id_b, reserved[1]);
+        assert_eq!(vec_tad.get(reserved[1]), None);
+    }
+
+    #[test]
+    fn reserve_exhausted_test() {
+        let mut vec_tad: ReusableIndexVec<u32, PackedID> = ReusableIndexVec::with_key();
+        vec_tad.set_exhaustion_policy(ExhaustionPolicy::Error);
+        let mut id = vec_tad.add(0);
+        while id.stamp() < PackedID::MAX_STAMP {
+            vec_tad.remove(id).unwrap();
+            id = vec_tad.add(0);
+        }
+        vec_tad.remove(id).unwrap();
+
+        // The exhausted slot stays at the head of the free list, and reserving fails like adding.
+        assert_eq!(vec_tad.try_reserve_id().unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(vec_tad.try_reserve_id().unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(vec_tad.reserved_count(), 0);
+        assert_eq!(vec_tad.try_add(2).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(vec_tad.retired_slot_count(), 0);
+        assert_eq!(vec_tad.free_slot_count(), 1);
+
+        // Without the `Error` policy, it is skipped and retired.
+        vec_tad.set_exhaustion_policy(ExhaustionPolicy::Retire);
+        let reserved = vec_tad.reserve_id();
+        assert_eq!(reserved.index(), 1);
+        vec_tad.flush_reserved(|_| Some(3));
+        assert_eq!(vec_tad.retired_slot_count(), 1);
+        assert_eq!(vec_tad.get(reserved), Some(&3));
+    }
+
+    #[test]
+    fn reserve_threads_test() {
+        let mut vec_tad: ReusableIndexVec<usize> = (0..100).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for id in ids.iter().step_by(2) {
+            vec_tad.remove(*id).unwrap();
+        }
+
+        // Four threads reserve 25 IDs each, while the vector is shared.
+        let shared = &vec_tad;
+        let mut reserved: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| scope.spawn(move || (0..25).map(|_| shared.reserve_id()).collect::<Vec<_>>()))
+                .collect();
+            handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect()
+        });
+        reserved.sort();
+        reserved.dedup();
+        assert_eq!(reserved.len(), 100);
+        assert_eq!(vec_tad.reserved_count(), 100);
+
+        vec_tad.flush_reserved(|id| Some(id.index()));
+        assert_eq!(vec_tad.len(), 150);
+        assert_eq!(vec_tad.free_slot_count(), 0);
+        for id in reserved {
+            assert_eq!(vec_tad.get(id), Some(&id.index()));
+        }
+    }
+}
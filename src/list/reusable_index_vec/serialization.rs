@@ -0,0 +1,138 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{Index, Key};
+
+use super::{ExhaustionPolicy, FreeListPolicy, ReusableIndexNode, ReusableIndexVec};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+// The saved form of a `ReusableIndexVec`. Only what cannot be worked out from the nodes is saved.
+#[derive(Serialize)]
+#[serde(rename = "ReusableIndexVec", bound = "T: Serialize, K::Stamp: Serialize, K::Index: Serialize")]
+struct SavedVecRef<'vec, T, K: Key> {
+    vector: &'vec [ReusableIndexNode<T, K>],
+    last_removed: Option<Index>,
+    fresh_stamp: K::Stamp,
+    exhaustion_policy: ExhaustionPolicy,
+    free_list_policy: FreeListPolicy,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "ReusableIndexVec", bound = "T: Deserialize<'de>, K::Stamp: Deserialize<'de>, K::Index: Deserialize<'de>")]
+struct SavedVec<T, K: Key> {
+    vector: Vec<ReusableIndexNode<T, K>>,
+    last_removed: Option<Index>,
+    fresh_stamp: K::Stamp,
+    exhaustion_policy: ExhaustionPolicy,
+    free_list_policy: FreeListPolicy,
+}
+
+/// The vector is saved with every slot, removed or not, so that the IDs, the cycle stamps and the
+/// order of the free list are the same after loading it.
+impl<T, K> Serialize for ReusableIndexVec<T, K>
+where T: Serialize, K: Key, K::Stamp: Serialize, K::Index: Serialize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedVecRef {
+            vector: self.vector.as_slice(),
+            last_removed: self.last_removed,
+            fresh_stamp: self.fresh_stamp,
+            exhaustion_policy: self.exhaustion_policy,
+            free_list_policy: self.free_list_policy,
+        }.serialize(serializer)
+    }
+}
+
+/// Loading fails if the free list is corrupt: if it has a cycle, goes out of bounds, goes through
+/// an existing node or misses a removed one.
+impl<'de, T, K> Deserialize<'de> for ReusableIndexVec<T, K>
+where T: Deserialize<'de>, K: Key, K::Stamp: Deserialize<'de>, K::Index: Deserialize<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = SavedVec::<T, K>::deserialize(deserializer)?;
+
+        Self::from_raw_parts(saved.vector, saved.last_removed, saved.fresh_stamp, saved.exhaustion_policy, saved.free_list_policy)
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::{PackedID, ID};
+
+    #[test]
+    fn round_trip_test() {
+        let mut vec_tad: ReusableIndexVec<String> = ["A", "B", "C", "D", "E"].iter().map(|s| String::from(*s)).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[3]).unwrap();
+        vec_tad.remove(ids[1]).unwrap();
+
+        let saved = serde_json::to_string(&vec_tad).unwrap();
+        let saved_ids = serde_json::to_string(&ids).unwrap();
+
+        let mut loaded: ReusableIndexVec<String> = serde_json::from_str(&saved).unwrap();
+        let loaded_ids: Vec<ID<String>> = serde_json::from_str(&saved_ids).unwrap();
+
+        assert_eq!(loaded_ids, ids);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded.free_slot_count(), 2);
+        for id in ids {
+            assert_eq!(loaded.get(id), vec_tad.get(id));
+        }
+
+        // The free list is the same, so the next IDs are the same as well.
+        for s in &["F", "G", "H"] {
+            assert_eq!(loaded.add(String::from(*s)), vec_tad.add(String::from(*s)));
+        }
+    }
+
+    #[test]
+    fn packed_round_trip_test() {
+        let mut vec_tad = ReusableIndexVec::<u32, PackedID>::with_key();
+        let id = vec_tad.add(1);
+        vec_tad.remove(id).unwrap();
+        let id = vec_tad.add(2);
+
+        let loaded: ReusableIndexVec<u32, PackedID> = serde_json::from_str(&serde_json::to_string(&vec_tad).unwrap()).unwrap();
+        let loaded_id: ID<u32, PackedID> = serde_json::from_str(&serde_json::to_string(&id).unwrap()).unwrap();
+
+        assert_eq!(loaded.get(loaded_id), Some(&2));
+    }
+
+    #[test]
+    fn corrupt_free_list_test() {
+        let load = |json: &str| serde_json::from_str::<ReusableIndexVec<u32>>(json);
+        let policy = r#""exhaustion_policy":"Wrap","free_list_policy":"Lifo","fresh_stamp":0"#;
+
+        // Sane
+        assert!(load(&format!(r#"{{"vector":[{{"Exists":[0,1]}},{{"Removed":0}}],"last_removed":1,{}}}"#, policy)).is_ok());
+        // Out of bounds
+        assert!(load(&format!(r#"{{"vector":[{{"Exists":[0,1]}},{{"Removed":0}}],"last_removed":2,{}}}"#, policy)).is_err());
+        // Points to an existing node
+        assert!(load(&format!(r#"{{"vector":[{{"Exists":[0,1]}},{{"Removed":0}}],"last_removed":0,{}}}"#, policy)).is_err());
+        // Cycle
+        assert!(load(&format!(r#"{{"vector":[{{"RemovedAndNext":[0,1]}},{{"RemovedAndNext":[0,0]}}],"last_removed":0,{}}}"#, policy)).is_err());
+        // Removed node left out
+        assert!(load(&format!(r#"{{"vector":[{{"Removed":0}},{{"Removed":0}}],"last_removed":0,{}}}"#, policy)).is_err());
+    }
+}
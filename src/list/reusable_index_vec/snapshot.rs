@@ -0,0 +1,606 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::error::{Error as BugeError, ErrorType as BugeErrorType};
+
+use crate::list::{Index, Key, KeyIndex, ListResult, Stamp};
+
+use super::{ExhaustionPolicy, FreeListPolicy, ReusableIndexNode, ReusableIndexVec};
+
+/// Encodes and decodes elements of a `ReusableIndexVec` for its binary snapshots.
+///
+/// Implemented for the integer and floating point types, `bool` and `String`. These can be used as
+/// building blocks for the implementation of other types.
+///
+/// ```
+///     use bugeutils::list::{ListResult, ReusableIndexVec, SnapshotCodec};
+///
+///     struct Particle {
+///         position: (f32, f32),
+///         ttl: u32,
+///     }
+///
+///     impl SnapshotCodec for Particle {
+///         fn encode(&self, out: &mut Vec<u8>) {
+///             self.position.0.encode(out);
+///             self.position.1.encode(out);
+///             self.ttl.encode(out);
+///         }
+///
+///         fn decode(input: &mut &[u8]) -> ListResult<Self> {
+///             Ok(Particle {
+///                 position: (f32::decode(input)?, f32::decode(input)?),
+///                 ttl: u32::decode(input)?,
+///             })
+///         }
+///     }
+///
+///     let mut particles = ReusableIndexVec::new();
+///     let id = particles.add(Particle { position: (1.0, 2.0), ttl: 60 });
+///
+///     let snapshot = particles.to_snapshot();
+///     let mut loaded = ReusableIndexVec::<Particle>::from_snapshot(&snapshot).unwrap();
+///
+///     assert_eq!(loaded.get(id).unwrap().ttl, 60);
+/// ```
+pub trait SnapshotCodec: Sized {
+    /// Appends the encoded value to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a value from the start of `input`, advancing it past the bytes that were read.
+    ///
+    /// # Errors
+    /// Should return error of type `InvalidParameter` if `input` does not hold a valid value.
+    fn decode(input: &mut &[u8]) -> ListResult<Self>;
+}
+
+fn corrupt(desc: &str) -> BugeError {
+    BugeError::new(BugeErrorType::InvalidParameter, &format!("corrupt snapshot, {}", desc))
+}
+
+fn take_bytes<'a>(input: &mut &'a [u8], count: usize) -> ListResult<&'a [u8]> {
+    if input.len() < count {
+        return Err(corrupt("unexpected end of data"));
+    }
+
+    let (bytes, rest) = input.split_at(count);
+    *input = rest;
+    Ok(bytes)
+}
+
+macro_rules! impl_snapshot_codec {
+    ($($num:ty),*) => {$(
+        impl SnapshotCodec for $num {
+            #[inline]
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(input: &mut &[u8]) -> ListResult<Self> {
+                let mut bytes = [0; std::mem::size_of::<$num>()];
+                bytes.copy_from_slice(take_bytes(input, std::mem::size_of::<$num>())?);
+                Ok(<$num>::from_le_bytes(bytes))
+            }
+        }
+    )*};
+}
+
+impl_snapshot_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl SnapshotCodec for bool {
+    #[inline]
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> ListResult<Self> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(corrupt("invalid bool")),
+        }
+    }
+}
+
+impl SnapshotCodec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_varint(out, self.len() as u64);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> ListResult<Self> {
+        let len = read_len(input)?;
+        let bytes = take_bytes(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| corrupt("invalid UTF-8 string"))
+    }
+}
+
+// Unsigned LEB128
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> ListResult<u64> {
+    let mut value = 0_u64;
+
+    for shift in (0..64).step_by(7) {
+        let byte = u8::decode(input)?;
+        let bits = (byte & 0x7f) as u64;
+
+        if shift == 63 && bits > 1 {
+            break;
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(corrupt("integer is too large"))
+}
+
+fn read_len(input: &mut &[u8]) -> ListResult<usize> {
+    let len = read_varint(input)?;
+    if len > input.len() as u64 {
+        // Every counted item takes at least one byte, so this cannot be right.
+        return Err(corrupt("length is larger than the data"));
+    }
+    Ok(len as usize)
+}
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"BRIV";
+const SNAPSHOT_VERSION: u8 = 1;
+
+// Tags of each run of slots.
+const RUN_EXISTS: u8 = 0;
+const RUN_REMOVED: u8 = 1;
+const RUN_RETIRED: u8 = 2;
+const RUN_RESERVED: u8 = 3;
+
+// The number of bits of the stamps of the key. Unlike `K::MAX_INDEX`, it does not depend on the
+// platform, so a snapshot made on a 64 bit machine loads on a 32 bit one.
+#[inline]
+fn stamp_bits<K: Key>() -> u64 {
+    64 - K::MAX_STAMP.to_u64().leading_zeros() as u64
+}
+
+// Encodes the link of a removed slot at `index`: zero for the end of the free list, otherwise the
+// distance to the next slot, zigzag encoded, plus one. Slots removed one after the other point
+// at their neighbours, so their links are all the same.
+#[inline]
+fn link_code(index: Index, next: Option<Index>) -> u64 {
+    match next {
+        Some(next) => {
+            let delta = (next as u64).wrapping_sub(index as u64) as i64;
+            (((delta << 1) ^ (delta >> 63)) as u64) + 1
+        },
+        None => 0,
+    }
+}
+
+// Undoes `link_code`, checking the link fits the key.
+fn decode_link<K: Key>(index: Index, code: u64) -> ListResult<Option<Index>> {
+    if code == 0 {
+        return Ok(None);
+    }
+
+    let zigzag = code - 1;
+    let delta = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+    let next = (index as u64).wrapping_add(delta as u64);
+    if next > K::MAX_INDEX as u64 {
+        return Err(corrupt("free list index is too large for the key"));
+    }
+    Ok(Some(next as Index))
+}
+
+// Reads the length of a run, which must be between one and `max`.
+fn read_run_len(input: &mut &[u8], max: usize) -> ListResult<usize> {
+    match read_varint(input)? {
+        len if len == 0 || len > max as u64 => Err(corrupt("invalid run length")),
+        len => Ok(len as usize),
+    }
+}
+
+/// # Binary snapshots
+///
+/// A snapshot holds every slot of the vector, so the IDs, cycle stamps and free list are the same
+/// after loading it. Reservations made since the vector was last borrowed mutably are not saved;
+/// older ones are, and can be flushed after loading. It is laid out as follows, integers being
+/// unsigned LEB128 unless noted:
+///
+/// * The magic bytes `BRIV` and the format version, one byte.
+/// * The number of bits of the cycle stamps of the key, so that a snapshot is not loaded with
+///   another key. The width of the indices is not saved, as it depends on the platform for
+///   `UntypedID`; instead, every index is checked against the key when loading.
+/// * The policies, one byte: the `FreeListPolicy` in the high four bits and the `ExhaustionPolicy`
+///   in the low four. Then the stamp of brand new slots, and the number of slots.
+/// * The free list head, plus one, or zero if the free list is empty.
+/// * Runs of slots in the same state: a tag byte, the length of the run, and then the slots. An
+///   existing slot is its stamp followed by the element, as encoded by `SnapshotCodec`. The other
+///   slots are run-length encoded: groups of neighbouring slots that have the same stamp, and the
+///   same link, are written as the length of the group followed by the stamp. For removed slots,
+///   the link follows: zero at the end of the free list, otherwise the distance from the slot to
+///   the next one on the free list, zigzag encoded, plus one.
+impl<T: SnapshotCodec, K: Key> ReusableIndexVec<T, K> {
+    /// Encodes the vector into a binary snapshot.
+    pub fn to_snapshot(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        out.extend_from_slice(SNAPSHOT_MAGIC);
+        out.push(SNAPSHOT_VERSION);
+        write_varint(&mut out, stamp_bits::<K>());
+        let exhaustion_policy = match self.exhaustion_policy {
+            ExhaustionPolicy::Wrap => 0,
+            ExhaustionPolicy::Retire => 1,
+            ExhaustionPolicy::Error => 2,
+        };
+        let free_list_policy = match self.free_list_policy {
+            FreeListPolicy::Lifo => 0,
+            FreeListPolicy::Fifo => 1,
+            FreeListPolicy::LowestIndexFirst => 2,
+        };
+        out.push(free_list_policy << 4 | exhaustion_policy);
+        write_varint(&mut out, self.fresh_stamp.to_u64());
+        write_varint(&mut out, self.vector.len() as u64);
+        write_varint(&mut out, self.last_removed.map_or(0, |index| index as u64 + 1));
+
+        let run_tag = |node: &ReusableIndexNode<T, K>| match node {
+            ReusableIndexNode::Exists(_, _) => RUN_EXISTS,
+            ReusableIndexNode::Removed(_) | ReusableIndexNode::RemovedAndNext(_, _) => RUN_REMOVED,
+            ReusableIndexNode::Retired(_) => RUN_RETIRED,
+            ReusableIndexNode::Reserved(_) => RUN_RESERVED,
+        };
+        // The stamp and link code of a slot that is not `Exists`.
+        let empty_slot = |index: Index| {
+            let node = &self.vector[index];
+            let next = match node {
+                ReusableIndexNode::RemovedAndNext(_, next) => Some(next.to_index()),
+                _ => None,
+            };
+            (node.cycle_stamp(), link_code(index, next))
+        };
+
+        let mut run_start = 0;
+        while run_start < self.vector.len() {
+            let tag = run_tag(&self.vector[run_start]);
+            let run_len = self.vector[run_start..].iter().take_while(|node| run_tag(node) == tag).count();
+            let run_end = run_start + run_len;
+
+            out.push(tag);
+            write_varint(&mut out, run_len as u64);
+
+            if tag == RUN_EXISTS {
+                for node in &self.vector[run_start..run_end] {
+                    if let ReusableIndexNode::Exists(cycle_stamp, value) = node {
+                        write_varint(&mut out, cycle_stamp.to_u64());
+                        value.encode(&mut out);
+                    }
+                }
+            } else {
+                let mut group_start = run_start;
+                while group_start < run_end {
+                    let (cycle_stamp, link) = empty_slot(group_start);
+                    let group_len = (group_start..run_end).take_while(|index| empty_slot(*index) == (cycle_stamp, link)).count();
+
+                    write_varint(&mut out, group_len as u64);
+                    write_varint(&mut out, cycle_stamp.to_u64());
+                    if tag == RUN_REMOVED {
+                        write_varint(&mut out, link);
+                    }
+
+                    group_start += group_len;
+                }
+            }
+
+            run_start = run_end;
+        }
+
+        out
+    }
+
+    /// Decodes a vector from a binary snapshot made by `to_snapshot`.
+    ///
+    /// # Errors
+    /// This function returns error of type `NotCompatible` if the snapshot was made by another
+    /// version of the format, with a key that has other cycle stamps, or has more slots than the
+    /// key `K` can index or than fit in memory. It returns error of type `InvalidParameter` if the
+    /// snapshot is corrupt, including when its free list is.
+    pub fn from_snapshot(mut input: &[u8]) -> ListResult<Self> {
+        let input = &mut input;
+
+        if take_bytes(input, SNAPSHOT_MAGIC.len())? != SNAPSHOT_MAGIC {
+            return Err(corrupt("this is not a ReusableIndexVec snapshot"));
+        }
+        let version = u8::decode(input)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(BugeError::new(BugeErrorType::NotCompatible, &format!("snapshot version {} is not supported", version)));
+        }
+        if read_varint(input)? != stamp_bits::<K>() {
+            return Err(BugeError::new(BugeErrorType::NotCompatible, "snapshot was made with another key"));
+        }
+
+        let policies = u8::decode(input)?;
+        let exhaustion_policy = match policies & 0x0F {
+            0 => ExhaustionPolicy::Wrap,
+            1 => ExhaustionPolicy::Retire,
+            2 => ExhaustionPolicy::Error,
+            _ => return Err(corrupt("invalid exhaustion policy")),
+        };
+        let free_list_policy = match policies >> 4 {
+            0 => FreeListPolicy::Lifo,
+            1 => FreeListPolicy::Fifo,
+            2 => FreeListPolicy::LowestIndexFirst,
+            _ => return Err(corrupt("invalid free list policy")),
+        };
+        let fresh_stamp = read_stamp::<K>(input)?;
+        let slot_count = read_varint(input)?;
+        if slot_count > 0 && slot_count - 1 > K::MAX_INDEX as u64 {
+            return Err(BugeError::new(BugeErrorType::NotCompatible, &format!("snapshot has {} slots, more than this key can index", slot_count)));
+        }
+        let slot_count = slot_count as usize;
+        let last_removed = read_link(input)?;
+
+        // Removed slots take next to no room, so the data does not tell how many there are.
+        let mut vector = Vec::with_capacity(slot_count.min(input.len()));
+        while vector.len() < slot_count {
+            let tag = u8::decode(input)?;
+            let run_len = read_run_len(input, slot_count - vector.len())?;
+            let run_end = vector.len() + run_len;
+
+            match tag {
+                RUN_EXISTS => {
+                    for _ in 0..run_len {
+                        let cycle_stamp = read_stamp::<K>(input)?;
+                        vector.push(ReusableIndexNode::Exists(cycle_stamp, T::decode(input)?));
+                    }
+                },
+                RUN_REMOVED | RUN_RETIRED | RUN_RESERVED => {
+                    while vector.len() < run_end {
+                        let group_len = read_run_len(input, run_end - vector.len())?;
+                        let cycle_stamp = read_stamp::<K>(input)?;
+                        let link = if tag == RUN_REMOVED { read_varint(input)? } else { 0 };
+
+                        // A few bytes stand for the whole group, so its length is not to be trusted.
+                        if vector.try_reserve(group_len).is_err() {
+                            return Err(BugeError::new(BugeErrorType::NotCompatible, &format!("snapshot has {} slots, more than fit in memory", slot_count)));
+                        }
+                        for _ in 0..group_len {
+                            vector.push(match tag {
+                                RUN_REMOVED => match decode_link::<K>(vector.len(), link)? {
+                                    Some(next) => ReusableIndexNode::RemovedAndNext(cycle_stamp, K::Index::from_index(next)),
+                                    None => ReusableIndexNode::Removed(cycle_stamp),
+                                },
+                                RUN_RETIRED => ReusableIndexNode::Retired(cycle_stamp),
+                                _ => ReusableIndexNode::Reserved(cycle_stamp),
+                            });
+                        }
+                    }
+                },
+                _ => return Err(corrupt("invalid run tag")),
+            }
+        }
+
+        if !input.is_empty() {
+            return Err(corrupt("unexpected data after the end"));
+        }
+
+        Self::from_raw_parts(vector, last_removed, fresh_stamp, exhaustion_policy, free_list_policy)
+    }
+}
+
+fn read_stamp<K: Key>(input: &mut &[u8]) -> ListResult<K::Stamp> {
+    let cycle_stamp = read_varint(input)?;
+    if cycle_stamp > K::MAX_STAMP.to_u64() {
+        return Err(corrupt("cycle stamp is too large for the key"));
+    }
+    Ok(K::Stamp::from_u64(cycle_stamp))
+}
+
+fn read_link(input: &mut &[u8]) -> ListResult<Option<Index>> {
+    match read_varint(input)? {
+        0 => Ok(None),
+        link if link - 1 > Index::MAX as u64 => Err(corrupt("index is too large")),
+        link => Ok(Some((link - 1) as Index)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::PackedID;
+
+    fn sample_vec() -> ReusableIndexVec<String> {
+        let mut vec_tad: ReusableIndexVec<String> = (0..8).map(|i| i.to_string()).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for i in &[6, 1, 2, 5] {
+            vec_tad.remove(ids[*i]).unwrap();
+        }
+        vec_tad.add(String::from("reused"));
+        vec_tad
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let mut vec_tad = sample_vec();
+        let snapshot = vec_tad.to_snapshot();
+        let mut loaded = ReusableIndexVec::<String>::from_snapshot(&snapshot).unwrap();
+
+        assert_eq!(loaded.len(), vec_tad.len());
+        assert_eq!(loaded.free_slot_count(), vec_tad.free_slot_count());
+
+        let original: Vec<_> = vec_tad.iter_with_ids().map(|(id, s)| (id, s.clone())).collect();
+        let restored: Vec<_> = loaded.iter_with_ids().map(|(id, s)| (id, s.clone())).collect();
+        assert_eq!(original, restored);
+
+        for _ in 0..4 {
+            assert_eq!(loaded.add(String::new()), vec_tad.add(String::new()));
+        }
+    }
+
+    #[test]
+    fn free_list_policy_test() {
+        let mut vec_tad = ReusableIndexVec::with_free_list_policy(FreeListPolicy::LowestIndexFirst);
+        vec_tad.extend(sample_vec().drain());
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        vec_tad.remove(ids[3]).unwrap();
+        vec_tad.remove(ids[0]).unwrap();
+
+        let mut loaded = ReusableIndexVec::<String>::from_snapshot(&vec_tad.to_snapshot()).unwrap();
+        assert_eq!(loaded.free_list_policy(), FreeListPolicy::LowestIndexFirst);
+        vec_tad.remove(ids[1]).unwrap();
+        loaded.remove(ids[1]).unwrap();
+        for _ in 0..4 {
+            assert_eq!(loaded.add(String::new()), vec_tad.add(String::new()));
+        }
+
+        // A free list that is out of order does not load with this policy.
+        let mut snapshot = b"BRIV\x01\x20".to_vec();
+        let policies_at = snapshot.len();
+        snapshot.extend_from_slice(&[0x20, 0, 2, 2, RUN_REMOVED, 2, 1, 0, 0, 1, 0, 2]);
+        let err = ReusableIndexVec::<u8>::from_snapshot(&snapshot).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::InvalidParameter);
+        snapshot[policies_at] = 0;
+        assert!(ReusableIndexVec::<u8>::from_snapshot(&snapshot).is_ok());
+    }
+
+    #[test]
+    fn key_mismatch_test() {
+        let snapshot = sample_vec().to_snapshot();
+        let err = ReusableIndexVec::<String, PackedID>::from_snapshot(&snapshot).unwrap_err();
+
+        assert_eq!(err.error_type(), BugeErrorType::NotCompatible);
+    }
+
+    // The default stamps, with indices as narrow as those of `UntypedID` on a tiny platform.
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+    struct TinyID(u32, u8);
+
+    impl Key for TinyID {
+        type Stamp = u32;
+        type Index = u16;
+        const MAX_STAMP: u32 = u32::MAX;
+        const MAX_INDEX: Index = u8::MAX as Index;
+
+        fn new(stamp: u32, index: Index) -> Self {
+            TinyID(stamp, index as u8)
+        }
+
+        fn stamp(&self) -> u32 {
+            self.0
+        }
+
+        fn index(&self) -> Index {
+            self.1 as Index
+        }
+    }
+
+    #[test]
+    fn narrower_index_test() {
+        // A snapshot loads with narrower indices, as long as every index fits.
+        let mut loaded = ReusableIndexVec::<String, TinyID>::from_snapshot(&sample_vec().to_snapshot()).unwrap();
+        let original = sample_vec();
+        let restored: Vec<_> = loaded.iter_with_ids().map(|(id, s)| (id.stamp(), id.index(), s.clone())).collect();
+        assert_eq!(restored, original.iter_with_ids().map(|(id, s)| (id.stamp(), id.index(), s.clone())).collect::<Vec<_>>());
+        assert_eq!(loaded.add(String::new()).index(), 2);
+
+        let too_long: ReusableIndexVec<u8> = (0..=255).chain(0..10).collect();
+        let err = ReusableIndexVec::<u8, TinyID>::from_snapshot(&too_long.to_snapshot()).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::NotCompatible);
+    }
+
+    #[test]
+    fn removed_runs_test() {
+        let mut vec_tad: ReusableIndexVec<u8> = (0..=255).cycle().take(3_000).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for id in &ids[100..2_900] {
+            vec_tad.remove(*id).unwrap();
+        }
+
+        // The removed slots all link to their neighbours with the same stamp, so they take a
+        // handful of bytes.
+        let snapshot = vec_tad.to_snapshot();
+        assert!(snapshot.len() < 200 * 2 + 32, "snapshot takes {} bytes", snapshot.len());
+
+        let mut loaded = ReusableIndexVec::<u8>::from_snapshot(&snapshot).unwrap();
+        assert_eq!(loaded.free_slot_count(), 2_800);
+        assert!(loaded.check_integrity().is_ok());
+        for _ in 0..10 {
+            assert_eq!(loaded.add(0), vec_tad.add(0));
+        }
+    }
+
+    #[test]
+    fn corrupt_snapshot_test() {
+        let snapshot = sample_vec().to_snapshot();
+
+        // Every truncation is caught.
+        for len in 0..snapshot.len() {
+            assert!(ReusableIndexVec::<String>::from_snapshot(&snapshot[..len]).is_err());
+        }
+
+        // Builds a snapshot of two slots by hand, with the given free list head and nodes.
+        let build = |head: u8, nodes: &[u8]| {
+            let mut snapshot = b"BRIV\x01\x20".to_vec();
+            snapshot.extend_from_slice(&[0, 0, 2, head]);
+            snapshot.extend_from_slice(nodes);
+            snapshot
+        };
+        let load = |snapshot: &[u8]| ReusableIndexVec::<u8>::from_snapshot(snapshot).map(|_| ()).map_err(|e| e.error_type());
+
+        // Sane: an existing node with value 7, then a removed node at the end of the free list.
+        assert!(load(&build(2, &[RUN_EXISTS, 1, 0, 7, RUN_REMOVED, 1, 1, 0, 0])).is_ok());
+        // Free list head out of bounds
+        assert_eq!(load(&build(3, &[RUN_EXISTS, 1, 0, 7, RUN_REMOVED, 1, 1, 0, 0])), Err(BugeErrorType::InvalidParameter));
+        // Free list head on an existing node
+        assert_eq!(load(&build(1, &[RUN_EXISTS, 1, 0, 7, RUN_REMOVED, 1, 1, 0, 0])), Err(BugeErrorType::InvalidParameter));
+        // Free list cycle: 0 -> 1 -> 0
+        assert_eq!(load(&build(1, &[RUN_REMOVED, 2, 1, 0, 3, 1, 0, 2])), Err(BugeErrorType::InvalidParameter));
+        // Free list link out of bounds
+        assert_eq!(load(&build(1, &[RUN_REMOVED, 2, 1, 0, 5, 1, 0, 0])), Err(BugeErrorType::InvalidParameter));
+        // Group longer than its run
+        assert_eq!(load(&build(0, &[RUN_RETIRED, 1, 2, 0, RUN_RETIRED, 1, 1, 0])), Err(BugeErrorType::InvalidParameter));
+        // Run longer than the vector
+        assert_eq!(load(&build(0, &[RUN_EXISTS, 3, 0, 7, 0, 7, 0, 7])), Err(BugeErrorType::InvalidParameter));
+    }
+
+    #[test]
+    fn oversized_snapshot_test() {
+        // A dozen bytes claiming a retired group of 2^62 slots, far more than can be allocated.
+        let slot_count = 1u64 << 62;
+        let mut snapshot = b"BRIV\x01\x20".to_vec();
+        snapshot.extend_from_slice(&[0, 0]);
+        write_varint(&mut snapshot, slot_count);
+        snapshot.extend_from_slice(&[0, RUN_RETIRED]);
+        write_varint(&mut snapshot, slot_count);
+        write_varint(&mut snapshot, slot_count);
+        snapshot.push(0);
+
+        let err = ReusableIndexVec::<u8>::from_snapshot(&snapshot).map(|_| ()).unwrap_err();
+        assert_eq!(err.error_type(), BugeErrorType::NotCompatible);
+    }
+}
@@ -0,0 +1,171 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use std::mem;
+
+use crate::list::{Key, Stamp};
+
+use super::{ReusableIndexNode, ReusableIndexVec};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// How a `ReusableIndexVec` uses its memory, see `ReusableIndexVec::stats`.
+pub struct ReusableIndexStats {
+    /// The number of existing elements.
+    pub live_count: usize,
+    /// The number of slots in the vector, existing or not.
+    pub slot_count: usize,
+    /// The number of slots the vector can hold without allocating.
+    pub capacity: usize,
+    /// The number of removed slots waiting on the free list to be reused.
+    pub free_count: usize,
+    /// The number of slots that ran out of cycle stamps and will never be used again.
+    pub retired_count: usize,
+    /// The number of reserved IDs that have not been flushed yet.
+    pub reserved_count: usize,
+    /// The size of a single slot, in bytes.
+    pub node_bytes: usize,
+    /// The bytes taken by the slots in the vector, existing or not.
+    pub used_bytes: usize,
+    /// The bytes allocated for slots, which is `capacity` times `node_bytes`.
+    pub allocated_bytes: usize,
+    /// The length of the longest run of consecutive slots that hold no element, removed or
+    /// retired. Iterating over the vector has to step over them.
+    pub largest_removed_run: usize,
+    /// How many slots there are for each range of cycle stamps, which tells how often the slots
+    /// are being reused. The first entry counts the slots at stamp zero, and entry `n` counts the
+    /// slots with a stamp from `2^(n - 1)` up to, not including, `2^n`. Trailing empty ranges are
+    /// left out.
+    pub stamp_histogram: Vec<usize>,
+}
+
+impl ReusableIndexStats {
+    #[inline]
+    /// Returns the share of the slots that hold no element, from zero to one. Zero for an empty
+    /// vector.
+    pub fn fragmentation(&self) -> f64 {
+        if self.slot_count == 0 {
+            0.0
+        } else {
+            (self.slot_count - self.live_count) as f64 / self.slot_count as f64
+        }
+    }
+}
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Gathers statistics about how the vector uses its memory. This walks every slot.
+    ///
+    /// # Examples
+    /// ```
+    /// use bugeutils::list::ReusableIndexVec;
+    ///
+    /// let mut vec: ReusableIndexVec<u64> = (0..10).collect();
+    /// let ids: Vec<_> = vec.iter_with_ids().map(|(id, _)| id).collect();
+    /// for id in &ids[2..6] {
+    ///     vec.remove(*id).unwrap();
+    /// }
+    ///
+    /// let stats = vec.stats();
+    /// assert_eq!(stats.live_count, 6);
+    /// assert_eq!(stats.largest_removed_run, 4);
+    /// if stats.fragmentation() > 0.3 {
+    ///     vec.compact();
+    /// }
+    /// ```
+    pub fn stats(&self) -> ReusableIndexStats {
+        let node_bytes = mem::size_of::<ReusableIndexNode<T, K>>();
+
+        let mut largest_removed_run = 0;
+        let mut removed_run = 0;
+        let mut stamp_histogram = Vec::new();
+        for node in self.vector.iter() {
+            match node {
+                ReusableIndexNode::Removed(_) |
+                ReusableIndexNode::RemovedAndNext(_, _) |
+                ReusableIndexNode::Retired(_) => {
+                    removed_run += 1;
+                    largest_removed_run = largest_removed_run.max(removed_run);
+                },
+                _ => removed_run = 0,
+            }
+
+            let bucket = (u64::BITS - node.cycle_stamp().to_u64().leading_zeros()) as usize;
+            if stamp_histogram.len() <= bucket {
+                stamp_histogram.resize(bucket + 1, 0);
+            }
+            stamp_histogram[bucket] += 1;
+        }
+
+        ReusableIndexStats {
+            live_count: self.live_count,
+            slot_count: self.vector.len(),
+            capacity: self.vector.capacity(),
+            free_count: self.free_count,
+            retired_count: self.retired_count,
+            reserved_count: self.reserved_count(),
+            node_bytes,
+            used_bytes: self.vector.len() * node_bytes,
+            allocated_bytes: self.vector.capacity() * node_bytes,
+            largest_removed_run,
+            stamp_histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_test() {
+        let empty = ReusableIndexVec::<u32>::with_capacity(4).stats();
+        assert_eq!(empty.slot_count, 0);
+        assert_eq!(empty.capacity, 4);
+        assert_eq!(empty.allocated_bytes, 4 * empty.node_bytes);
+        assert_eq!(empty.stamp_histogram, Vec::<usize>::new());
+        assert_eq!(empty.fragmentation(), 0.0);
+
+        let mut vec_tad: ReusableIndexVec<u32> = (0..8).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        for index in [1, 2, 3, 5, 6].iter() {
+            vec_tad.remove(ids[*index]).unwrap();
+        }
+        // Slot 6, the last one removed, goes through stamps 1, 2 and 3.
+        for _ in 0..3 {
+            let id = vec_tad.add(10);
+            vec_tad.remove(id).unwrap();
+        }
+        let _reserved = vec_tad.reserve_id();
+
+        let stats = vec_tad.stats();
+        assert_eq!(stats.live_count, 3);
+        assert_eq!(stats.slot_count, 8);
+        assert_eq!(stats.free_count, 5);
+        assert_eq!(stats.retired_count, 0);
+        assert_eq!(stats.reserved_count, 1);
+        assert_eq!(stats.node_bytes, mem::size_of::<ReusableIndexNode<u32>>());
+        assert_eq!(stats.used_bytes, 8 * stats.node_bytes);
+        assert_eq!(stats.largest_removed_run, 3);
+        assert_eq!(stats.stamp_histogram, vec![7, 0, 1]);
+        assert_eq!(stats.fragmentation(), 5.0 / 8.0);
+    }
+}
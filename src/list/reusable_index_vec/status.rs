@@ -0,0 +1,134 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+#[cfg(all(feature = "debug-removals", debug_assertions))]
+use crate::list::Index;
+use crate::list::{CycleStamp, Key, ID};
+
+use super::{slot_status, ReusableIndexVec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// What an ID refers to in a `ReusableIndexVec`, see `ReusableIndexVec::status`.
+pub enum ReusableIndexStatus<S = CycleStamp> {
+    /// The ID refers to an element of the vector.
+    Live,
+    /// The ID was given by `ReusableIndexVec::reserve_id`, and has not been flushed yet.
+    Reserved,
+    /// The element was removed, and its slot is empty. `stamp` is the stamp of the last element
+    /// that lived in the slot.
+    Removed { stamp: S },
+    /// The element was removed, and its slot now holds another element, with `current_stamp`.
+    Reused { current_stamp: S },
+    /// The ID has never been valid: its index is past the end of the vector, which has `len`
+    /// slots.
+    OutOfBounds { len: usize },
+}
+
+impl<T, K: Key> ReusableIndexVec<T, K> {
+    /// Tells what the given ID refers to, which is useful to understand why a lookup failed.
+    ///
+    /// With the `debug-removals` feature, debug builds also remember where each slot was last
+    /// freed, and the errors for expired IDs say it.
+    ///
+    /// # Examples
+    /// ```
+    /// use bugeutils::list::{ReusableIndexStatus, ReusableIndexVec};
+    ///
+    /// let mut vec = ReusableIndexVec::new();
+    /// let id = vec.add("first");
+    /// assert_eq!(vec.status(id), ReusableIndexStatus::Live);
+    ///
+    /// vec.remove(id).unwrap();
+    /// assert_eq!(vec.status(id), ReusableIndexStatus::Removed { stamp: 0 });
+    ///
+    /// vec.add("second");
+    /// assert_eq!(vec.status(id), ReusableIndexStatus::Reused { current_stamp: 1 });
+    /// ```
+    pub fn status(&self, id: ID<T, K>) -> ReusableIndexStatus<K::Stamp> {
+        match slot_status(self.vector.get(id.index()), id.stamp(), self.vector.len()) {
+            ReusableIndexStatus::Removed { .. } |
+            ReusableIndexStatus::OutOfBounds { .. } if self.is_pending_reservation(id) => ReusableIndexStatus::Reserved,
+            status => status,
+        }
+    }
+
+    #[cfg(all(feature = "debug-removals", debug_assertions))]
+    #[track_caller]
+    #[inline]
+    // Remembers that the slot at `index` is being freed by the caller.
+    pub(super) fn record_removal(&mut self, index: Index) {
+        self.removed_at.record(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::UntypedID;
+
+    #[test]
+    fn status_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        assert_eq!(vec_tad.status(ids[1]), ReusableIndexStatus::Live);
+        assert_eq!(vec_tad.status(UntypedID(0, 5).typed()), ReusableIndexStatus::OutOfBounds { len: 3 });
+
+        vec_tad.remove(ids[1]).unwrap();
+        assert_eq!(vec_tad.status(ids[1]), ReusableIndexStatus::Removed { stamp: 0 });
+
+        let reserved = vec_tad.reserve_id();
+        let reserved_past_end = vec_tad.reserve_id();
+        assert_eq!(vec_tad.status(reserved), ReusableIndexStatus::Reserved);
+        assert_eq!(vec_tad.status(reserved_past_end), ReusableIndexStatus::Reserved);
+        assert_eq!(vec_tad.status(UntypedID(0, 4).typed()), ReusableIndexStatus::OutOfBounds { len: 3 });
+        vec_tad.flush_reserved(|id| if id == reserved { None } else { Some(3) });
+        let reused = vec_tad.add(10);
+        assert_eq!(vec_tad.status(reused), ReusableIndexStatus::Live);
+        assert_eq!(vec_tad.status(reserved), ReusableIndexStatus::Reused { current_stamp: 2 });
+        assert_eq!(vec_tad.status(ids[1]), ReusableIndexStatus::Reused { current_stamp: 2 });
+
+        vec_tad.remove(reused).unwrap();
+        assert_eq!(vec_tad.status(ids[1]), ReusableIndexStatus::Removed { stamp: 2 });
+    }
+
+    #[cfg(all(feature = "debug-removals", debug_assertions))]
+    #[test]
+    fn removal_location_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..3).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+
+        let line = line!() + 1;
+        vec_tad.remove(ids[1]).unwrap();
+        let err = vec_tad.try_get(ids[1]).unwrap_err();
+        assert!(err.to_string().contains(&format!("freed at {}:{}:", file!(), line)), "{}", err);
+
+        let line = line!() + 1;
+        vec_tad.retain(|_, node| *node != 2);
+        let err = vec_tad.remove(ids[2]).unwrap_err();
+        assert!(err.to_string().contains(&format!("freed at {}:{}:", file!(), line)), "{}", err);
+
+        // Nothing is known about slots that were never freed.
+        let err = vec_tad.try_get(UntypedID(1, 0).typed()).unwrap_err();
+        assert!(!err.to_string().contains("freed at"), "{}", err);
+    }
+}
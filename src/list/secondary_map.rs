@@ -0,0 +1,660 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::{Index, Key, UntypedID, ID};
+
+use std::collections::{hash_map, HashMap};
+use std::iter::{Enumerate, FusedIterator};
+use std::marker::PhantomData;
+use std::{fmt, ops, slice};
+
+// A slot of a `SecondaryMap`: the value, with the stamp of its ID.
+type Slot<V, K> = Option<(<K as Key>::Stamp, V)>;
+
+// Whether a value for an ID with `stamp` goes into a slot holding a value for `stored`.
+enum SlotUpdate {
+    // The slot holds a value for the same ID.
+    Replace,
+    // The slot is empty, or holds a value for an older ID.
+    Overwrite,
+    // The slot holds a value for a newer ID, so the ID being inserted is stale.
+    Ignore,
+}
+
+fn slot_update<S: Ord>(stored: Option<S>, stamp: S) -> SlotUpdate {
+    match stored {
+        Some(stored) if stored == stamp => SlotUpdate::Replace,
+        Some(stored) if stored > stamp => SlotUpdate::Ignore,
+        _ => SlotUpdate::Overwrite,
+    }
+}
+
+/// Extra values for some of the elements of a `ReusableIndexVec`, looked up by their IDs.
+///
+/// The values are stored in a vector indexed like the `ReusableIndexVec`, which makes lookups
+/// as fast as in the `ReusableIndexVec` itself, at the cost of a slot for every index up to the
+/// highest one inserted. See `SparseSecondaryMap` for values attached to few elements.
+///
+/// Each value remembers the cycle stamp of its ID. A value inserted for an element that has since
+/// been removed is not found with the ID of the element that reuses the slot, and is overwritten
+/// when a value is inserted for the new element.
+///
+/// ```
+/// use bugeutils::list::{ReusableIndexVec, SecondaryMap};
+///
+/// let mut entities = ReusableIndexVec::new();
+/// let mut names = SecondaryMap::new();
+///
+/// let player = entities.add("player");
+/// names.insert(player, "Alice").unwrap();
+/// assert_eq!(names.get(player), Some(&"Alice"));
+///
+/// entities.remove(player).unwrap();
+/// let monster = entities.add("monster");
+/// assert_eq!(monster.index(), player.index());
+/// assert_eq!(names.get(monster), None);
+/// ```
+pub struct SecondaryMap<T, V, K: Key = UntypedID> {
+    slots: Vec<Slot<V, K>>,
+    len: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, V, K: Key> SecondaryMap<T, V, K> {
+    #[inline]
+    /// Creates a new empty `SecondaryMap`.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    #[inline]
+    /// Creates a new empty `SecondaryMap` with room for the indices below `capacity`.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            len: 0,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Returns the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    /// Returns `true` if there are no values in the map.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts `value` for the given ID, giving back the value that was there for the same ID.
+    ///
+    /// A value left by an older ID with the same index is dropped. IDs are told apart by their
+    /// cycle stamps, the higher stamp being the newer ID. Once a slot wraps its stamps around,
+    /// with `ExhaustionPolicy::Wrap`, a live ID can look older than the value left in the map, so
+    /// the old value has to be removed before inserting the new one.
+    ///
+    /// # Errors
+    /// If the map already holds a value for a newer ID with the same index, `id` is stale and
+    /// `value` is given back untouched. This includes a live ID whose slot wrapped its stamps
+    /// around since the value in the map was inserted.
+    pub fn insert(&mut self, id: ID<T, K>, value: V) -> Result<Option<V>, V> {
+        let index = id.index();
+        if index >= self.slots.len() {
+            self.slots.resize_with(index + 1, || None);
+        }
+
+        let slot = &mut self.slots[index];
+        match slot_update(slot.as_ref().map(|(stamp, _)| *stamp), id.stamp()) {
+            SlotUpdate::Replace => Ok(slot.replace((id.stamp(), value)).map(|(_, old_value)| old_value)),
+            SlotUpdate::Overwrite => {
+                if slot.replace((id.stamp(), value)).is_none() {
+                    self.len += 1;
+                }
+                Ok(None)
+            },
+            SlotUpdate::Ignore => Err(value),
+        }
+    }
+
+    /// Removes the value for the given ID, giving it back.
+    pub fn remove(&mut self, id: ID<T, K>) -> Option<V> {
+        let slot = self.slots.get_mut(id.index())?;
+        match slot {
+            Some((stamp, _)) if *stamp == id.stamp() => {
+                self.len -= 1;
+                slot.take().map(|(_, value)| value)
+            },
+            _ => None,
+        }
+    }
+
+    #[inline]
+    /// Returns `true` if there is a value for the given ID.
+    pub fn contains_key(&self, id: ID<T, K>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns a reference to the value for the given ID.
+    pub fn get(&self, id: ID<T, K>) -> Option<&V> {
+        match self.slots.get(id.index()) {
+            Some(Some((stamp, value))) if *stamp == id.stamp() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value for the given ID.
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut V> {
+        match self.slots.get_mut(id.index()) {
+            Some(Some((stamp, value))) if *stamp == id.stamp() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Keeps only the values for which `keep` returns `true`. Useful to drop the values of the
+    /// elements that were removed from the `ReusableIndexVec`, with `ReusableIndexVec::contains`.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where F: FnMut(ID<T, K>, &mut V) -> bool {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some((stamp, value)) = slot {
+                if !keep(ID::from_untyped(K::new(*stamp, index)), value) {
+                    *slot = None;
+                    self.len -= 1;
+                }
+            }
+        }
+    }
+
+    /// Removes every value.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and values in the map, by index.
+    pub fn iter(&self) -> SecondaryMapIter<'_, T, V, K> {
+        SecondaryMapIter { slots: self.slots.iter().enumerate(), remaining: self.len, marker: PhantomData }
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and mutable values in the map, by index.
+    pub fn iter_mut(&mut self) -> SecondaryMapIterMut<'_, T, V, K> {
+        SecondaryMapIterMut { slots: self.slots.iter_mut().enumerate(), remaining: self.len, marker: PhantomData }
+    }
+}
+
+impl<T, V, K: Key> Default for SecondaryMap<T, V, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, V: Clone, K: Key> Clone for SecondaryMap<T, V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            len: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, V: fmt::Debug, K: Key> fmt::Debug for SecondaryMap<T, V, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Indexing with an ID that has no value panics. Use `get` where the value may be missing.
+impl<T, V, K: Key> ops::Index<ID<T, K>> for SecondaryMap<T, V, K> {
+    type Output = V;
+
+    fn index(&self, id: ID<T, K>) -> &V {
+        match self.get(id) {
+            Some(value) => value,
+            None => panic!("No value in SecondaryMap for ID {}", id),
+        }
+    }
+}
+
+impl<T, V, K: Key> ops::IndexMut<ID<T, K>> for SecondaryMap<T, V, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut V {
+        match self.get_mut(id) {
+            Some(value) => value,
+            None => panic!("No value in SecondaryMap for ID {}", id),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// An iterator over the IDs and values of a `SecondaryMap`.
+pub struct SecondaryMapIter<'map, T, V, K: Key = UntypedID> {
+    slots: Enumerate<slice::Iter<'map, Slot<V, K>>>,
+    remaining: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'map, T, V, K: Key> Iterator for SecondaryMapIter<'map, T, V, K> {
+    type Item = (ID<T, K>, &'map V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, slot) in &mut self.slots {
+            if let Some((stamp, value)) = slot {
+                self.remaining -= 1;
+                return Some((ID::from_untyped(K::new(*stamp, index)), value));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'map, T, V, K: Key> ExactSizeIterator for SecondaryMapIter<'map, T, V, K> {}
+impl<'map, T, V, K: Key> FusedIterator for SecondaryMapIter<'map, T, V, K> {}
+
+#[derive(Debug)]
+/// An iterator over the IDs and mutable values of a `SecondaryMap`.
+pub struct SecondaryMapIterMut<'map, T, V, K: Key = UntypedID> {
+    slots: Enumerate<slice::IterMut<'map, Slot<V, K>>>,
+    remaining: usize,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'map, T, V, K: Key> Iterator for SecondaryMapIterMut<'map, T, V, K> {
+    type Item = (ID<T, K>, &'map mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (index, slot) in &mut self.slots {
+            if let Some((stamp, value)) = slot {
+                self.remaining -= 1;
+                return Some((ID::from_untyped(K::new(*stamp, index)), value));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'map, T, V, K: Key> ExactSizeIterator for SecondaryMapIterMut<'map, T, V, K> {}
+impl<'map, T, V, K: Key> FusedIterator for SecondaryMapIterMut<'map, T, V, K> {}
+
+/// Like `SecondaryMap`, but the values are kept in a `HashMap` by index, so the map only takes
+/// memory for the values it holds. Lookups are slower.
+///
+/// ```
+/// use bugeutils::list::{ReusableIndexVec, SparseSecondaryMap};
+///
+/// let mut entities: ReusableIndexVec<u32> = (0..1000).collect();
+/// let mut selected = SparseSecondaryMap::new();
+///
+/// let id = entities.iter_with_ids().nth(500).unwrap().0;
+/// selected.insert(id, ()).unwrap();
+/// assert!(selected.contains_key(id));
+///
+/// entities.remove(id).unwrap();
+/// selected.retain(|id, _| entities.contains(id));
+/// assert!(selected.is_empty());
+/// ```
+pub struct SparseSecondaryMap<T, V, K: Key = UntypedID> {
+    slots: HashMap<Index, (K::Stamp, V)>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T, V, K: Key> SparseSecondaryMap<T, V, K> {
+    #[inline]
+    /// Creates a new empty `SparseSecondaryMap`.
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    #[inline]
+    /// Creates a new empty `SparseSecondaryMap` with room for `capacity` values.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: HashMap::with_capacity(capacity),
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Returns the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    /// Returns `true` if there are no values in the map.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Inserts `value` for the given ID, giving back the value that was there for the same ID.
+    ///
+    /// A value left by an older ID with the same index is dropped. As with `SecondaryMap`, once a
+    /// slot wraps its stamps around, with `ExhaustionPolicy::Wrap`, the old value has to be
+    /// removed before inserting the new one.
+    ///
+    /// # Errors
+    /// If the map already holds a value for a newer ID with the same index, `id` is stale and
+    /// `value` is given back untouched. This includes a live ID whose slot wrapped its stamps
+    /// around since the value in the map was inserted.
+    pub fn insert(&mut self, id: ID<T, K>, value: V) -> Result<Option<V>, V> {
+        match self.slots.entry(id.index()) {
+            hash_map::Entry::Occupied(mut entry) => match slot_update(Some(entry.get().0), id.stamp()) {
+                SlotUpdate::Replace => Ok(Some(entry.insert((id.stamp(), value)).1)),
+                SlotUpdate::Overwrite => {
+                    entry.insert((id.stamp(), value));
+                    Ok(None)
+                },
+                SlotUpdate::Ignore => Err(value),
+            },
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert((id.stamp(), value));
+                Ok(None)
+            },
+        }
+    }
+
+    /// Removes the value for the given ID, giving it back.
+    pub fn remove(&mut self, id: ID<T, K>) -> Option<V> {
+        match self.slots.entry(id.index()) {
+            hash_map::Entry::Occupied(entry) if entry.get().0 == id.stamp() => Some(entry.remove().1),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    /// Returns `true` if there is a value for the given ID.
+    pub fn contains_key(&self, id: ID<T, K>) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns a reference to the value for the given ID.
+    pub fn get(&self, id: ID<T, K>) -> Option<&V> {
+        match self.slots.get(&id.index()) {
+            Some((stamp, value)) if *stamp == id.stamp() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value for the given ID.
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut V> {
+        match self.slots.get_mut(&id.index()) {
+            Some((stamp, value)) if *stamp == id.stamp() => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Keeps only the values for which `keep` returns `true`. Useful to drop the values of the
+    /// elements that were removed from the `ReusableIndexVec`, with `ReusableIndexVec::contains`.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where F: FnMut(ID<T, K>, &mut V) -> bool {
+        self.slots.retain(|index, (stamp, value)| keep(ID::from_untyped(K::new(*stamp, *index)), value));
+    }
+
+    #[inline]
+    /// Removes every value.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and values in the map, in no particular order.
+    pub fn iter(&self) -> SparseSecondaryMapIter<'_, T, V, K> {
+        SparseSecondaryMapIter { slots: self.slots.iter(), marker: PhantomData }
+    }
+
+    #[inline]
+    /// Returns an iterator over the IDs and mutable values in the map, in no particular order.
+    pub fn iter_mut(&mut self) -> SparseSecondaryMapIterMut<'_, T, V, K> {
+        SparseSecondaryMapIterMut { slots: self.slots.iter_mut(), marker: PhantomData }
+    }
+}
+
+impl<T, V, K: Key> Default for SparseSecondaryMap<T, V, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, V: Clone, K: Key> Clone for SparseSecondaryMap<T, V, K> {
+    fn clone(&self) -> Self {
+        Self {
+            slots: self.slots.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, V: fmt::Debug, K: Key> fmt::Debug for SparseSecondaryMap<T, V, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Indexing with an ID that has no value panics. Use `get` where the value may be missing.
+impl<T, V, K: Key> ops::Index<ID<T, K>> for SparseSecondaryMap<T, V, K> {
+    type Output = V;
+
+    fn index(&self, id: ID<T, K>) -> &V {
+        match self.get(id) {
+            Some(value) => value,
+            None => panic!("No value in SparseSecondaryMap for ID {}", id),
+        }
+    }
+}
+
+impl<T, V, K: Key> ops::IndexMut<ID<T, K>> for SparseSecondaryMap<T, V, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut V {
+        match self.get_mut(id) {
+            Some(value) => value,
+            None => panic!("No value in SparseSecondaryMap for ID {}", id),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// An iterator over the IDs and values of a `SparseSecondaryMap`.
+pub struct SparseSecondaryMapIter<'map, T, V, K: Key = UntypedID> {
+    slots: hash_map::Iter<'map, Index, (K::Stamp, V)>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'map, T, V, K: Key> Iterator for SparseSecondaryMapIter<'map, T, V, K> {
+    type Item = (ID<T, K>, &'map V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(index, (stamp, value))| (ID::from_untyped(K::new(*stamp, *index)), value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<'map, T, V, K: Key> ExactSizeIterator for SparseSecondaryMapIter<'map, T, V, K> {}
+impl<'map, T, V, K: Key> FusedIterator for SparseSecondaryMapIter<'map, T, V, K> {}
+
+#[derive(Debug)]
+/// An iterator over the IDs and mutable values of a `SparseSecondaryMap`.
+pub struct SparseSecondaryMapIterMut<'map, T, V, K: Key = UntypedID> {
+    slots: hash_map::IterMut<'map, Index, (K::Stamp, V)>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'map, T, V, K: Key> Iterator for SparseSecondaryMapIterMut<'map, T, V, K> {
+    type Item = (ID<T, K>, &'map mut V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|(index, (stamp, value))| (ID::from_untyped(K::new(*stamp, *index)), value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<'map, T, V, K: Key> ExactSizeIterator for SparseSecondaryMapIterMut<'map, T, V, K> {}
+impl<'map, T, V, K: Key> FusedIterator for SparseSecondaryMapIterMut<'map, T, V, K> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::list::{PackedID, ReusableIndexVec};
+
+    #[test]
+    fn secondary_map_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..4).collect();
+        let ids: Vec<_> = vec_tad.iter_with_ids().map(|(id, _)| id).collect();
+        let mut map = SecondaryMap::new();
+
+        assert_eq!(map.insert(ids[2], "two"), Ok(None));
+        assert_eq!(map.insert(ids[0], "zero"), Ok(None));
+        assert_eq!(map.insert(ids[2], "TWO"), Ok(Some("two")));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[ids[2]], "TWO");
+        assert!(!map.contains_key(ids[1]));
+        assert_eq!(map.iter().map(|(id, value)| (id, *value)).collect::<Vec<_>>(), vec![(ids[0], "zero"), (ids[2], "TWO")]);
+
+        // The value of a removed element is not found through the element reusing its slot.
+        vec_tad.remove(ids[2]).unwrap();
+        let reused = vec_tad.add(20);
+        assert_eq!(map.get(reused), None);
+        assert_eq!(map.remove(reused), None);
+        assert_eq!(map.insert(reused, "reused"), Ok(None));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(ids[2]), None);
+
+        // A stale ID does not overwrite the newer value.
+        assert_eq!(map.insert(ids[2], "stale"), Err("stale"));
+        assert_eq!(map.get(reused), Some(&"reused"));
+
+        for (_, value) in map.iter_mut() {
+            *value = "changed";
+        }
+        map.retain(|id, _| id.index() != 0);
+        assert_eq!(map.iter().map(|(id, value)| (id, *value)).collect::<Vec<_>>(), vec![(reused, "changed")]);
+        assert_eq!(map.remove(reused), Some("changed"));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn sparse_secondary_map_test() {
+        let mut vec_tad = ReusableIndexVec::<u32, PackedID>::with_key();
+        let ids = vec_tad.add_many(0..4);
+        let mut map = SparseSecondaryMap::new();
+
+        assert_eq!(map.insert(ids[3], 3.0), Ok(None));
+        assert_eq!(map.insert(ids[3], 3.5), Ok(Some(3.0)));
+        assert_eq!(map.insert(ids[1], 1.0), Ok(None));
+        map[ids[1]] += 0.5;
+        assert_eq!(map.get(ids[1]), Some(&1.5));
+        assert_eq!(map.len(), 2);
+
+        vec_tad.remove(ids[3]).unwrap();
+        let reused = vec_tad.add(30);
+        assert!(!map.contains_key(reused));
+        assert_eq!(map.insert(reused, 30.0), Ok(None));
+        assert_eq!(map.insert(ids[3], -1.0), Err(-1.0));
+        assert_eq!(map.get(reused), Some(&30.0));
+        assert_eq!(map.remove(ids[3]), None);
+
+        map.retain(|id, _| id != ids[1]);
+        let mut entries: Vec<_> = map.iter_mut().map(|(id, value)| (id, *value)).collect();
+        entries.sort_by_key(|(id, _)| *id);
+        assert_eq!(entries, vec![(reused, 30.0)]);
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn stale_insert_test() {
+        let mut vec_tad: ReusableIndexVec<u32> = (0..2).collect();
+        let stale = vec_tad.iter_with_ids().nth(1).unwrap().0;
+        vec_tad.remove(stale).unwrap();
+        let reused = vec_tad.add(10);
+
+        // The value for a stale ID comes back whole, and the newer value stays.
+        let mut map = SecondaryMap::new();
+        map.insert(reused, String::from("reused")).unwrap();
+        assert_eq!(map.insert(stale, String::from("stale")), Err(String::from("stale")));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[reused], "reused");
+
+        let mut sparse = SparseSecondaryMap::new();
+        sparse.insert(reused, String::from("reused")).unwrap();
+        assert_eq!(sparse.insert(stale, String::from("stale")), Err(String::from("stale")));
+        assert_eq!(sparse.len(), 1);
+        assert_eq!(sparse[reused], "reused");
+    }
+
+    #[test]
+    fn wrapped_stamp_insert_test() {
+        let mut vec_tad = ReusableIndexVec::<u32, PackedID>::with_key();
+        let mut old = vec_tad.add(0);
+        while old.stamp() != PackedID::MAX_STAMP {
+            vec_tad.remove(old).unwrap();
+            old = vec_tad.add(0);
+        }
+        vec_tad.remove(old).unwrap();
+        let wrapped = vec_tad.add(1);
+        assert_eq!(wrapped.stamp(), 0);
+
+        // The live ID looks older than the value left by the last one, until that is removed.
+        let mut map = SecondaryMap::new();
+        map.insert(old, "old").unwrap();
+        assert_eq!(map.insert(wrapped, "wrapped"), Err("wrapped"));
+        assert_eq!(map.remove(old), Some("old"));
+        assert_eq!(map.insert(wrapped, "wrapped"), Ok(None));
+
+        let mut sparse = SparseSecondaryMap::new();
+        sparse.insert(old, "old").unwrap();
+        assert_eq!(sparse.insert(wrapped, "wrapped"), Err("wrapped"));
+        assert_eq!(sparse.remove(old), Some("old"));
+        assert_eq!(sparse.insert(wrapped, "wrapped"), Ok(None));
+    }
+
+    #[test]
+    #[should_panic(expected = "No value in SecondaryMap for ID 0::3")]
+    fn secondary_map_index_test() {
+        let map: SecondaryMap<u32, u32> = SecondaryMap::new();
+        let _ = map[UntypedID(0, 3).typed()];
+    }
+}
@@ -0,0 +1,901 @@
+/* *****************************************************************************
+ MIT License
+ 
+ Copyright (c) 2020 trindadegm
+ 
+ Permission is hereby granted, free of charge, to any person obtaining a copy
+ of this software and associated documentation files (the "Software"), to deal
+ in the Software without restriction, including without limitation the rights
+ to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+ copies of the Software, and to permit persons to whom the Software is
+ furnished to do so, subject to the following conditions:
+ 
+ The above copyright notice and this permission notice shall be included in all
+ copies or substantial portions of the Software.
+ 
+ THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+ IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+ FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+ AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+ OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+ SOFTWARE.
+***************************************************************************** */
+use crate::list::occupancy::{next_occupied, prev_occupied, Occupancy};
+use crate::list::reusable_index_vec::{
+    check_fresh_index, check_status, free_list_head, slot_status, FreeListHead, RemovalLog, DEFAULT_INITIAL_CAPACITY,
+};
+use crate::list::{ExhaustionPolicy, Index, Key, KeyIndex, ListResult, ReusableIndexAddError, ReusableIndexNode, Stamp, UntypedID, ID};
+
+use std::fmt;
+use std::iter::{FusedIterator, Zip};
+use std::marker::PhantomData;
+use std::mem::MaybeUninit;
+use std::{ops, slice};
+
+// The bookkeeping of a slot: its stamp and, when removed, its link on the free list. The value
+// lives in the matching slot of `values`, which is initialized only when this is `Exists`.
+type Slot<K> = ReusableIndexNode<(), K>;
+// The slots walked side by side with their values.
+type SlotsAndValues<'vec, T, K> = Zip<slice::Iter<'vec, Slot<K>>, slice::Iter<'vec, MaybeUninit<T>>>;
+
+/// A `ReusableIndexVec` that keeps the cycle stamps apart from the values.
+///
+/// The stamps and the free list links are kept in one array and the values in another, so
+/// checking an ID touches the stamps only, and a value takes exactly as much room as `T`. This
+/// pays off for small `T`, where the stamp would double the memory iteration goes through, and
+/// for `T` with a large alignment, such as `u128`, where the node gets padded. As in a
+/// `ReusableIndexVec`, iterating skips runs of removed slots without reading them. The IDs behave as
+/// in a `ReusableIndexVec` that reuses the most recently removed slot first.
+///
+/// There is no `ReusableIndexNode` to borrow, so `as_slice` gives a `SplitReusableIndexSlice`
+/// instead, which builds the nodes on the fly.
+///
+/// ```
+/// use bugeutils::list::{ReusableIndexNode, SplitReusableIndexVec};
+///
+/// let mut counters = SplitReusableIndexVec::new();
+/// let a = counters.add(1u8);
+/// let b = counters.add(2u8);
+///
+/// counters.remove(a).unwrap();
+/// for counter in counters.iter_mut() {
+///     *counter += 1;
+/// }
+/// assert_eq!(counters.get(b), Some(&3));
+/// assert_eq!(counters.get(a), None);
+///
+/// let nodes = counters.as_slice();
+/// assert!(matches!(nodes.get(0), Some(ReusableIndexNode::Removed(0))));
+/// assert!(matches!(nodes.get(1), Some(ReusableIndexNode::Exists(0, &3))));
+/// ```
+pub struct SplitReusableIndexVec<T, K: Key = UntypedID> {
+    slots: Vec<Slot<K>>,
+    // Always as long as `slots`.
+    values: Vec<MaybeUninit<T>>,
+    last_removed: Option<Index>,
+    live_count: usize,
+    exhaustion_policy: ExhaustionPolicy,
+    // Which slots hold an element, so that iterating can skip the others quickly.
+    occupied: Occupancy,
+    // Where each slot was last freed, to tell in the errors for expired IDs.
+    removed_at: RemovalLog,
+}
+
+impl<T> SplitReusableIndexVec<T> {
+    #[inline]
+    /// Creates a new empty `SplitReusableIndexVec`.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_INITIAL_CAPACITY)
+    }
+
+    #[inline]
+    /// Creates a new empty `SplitReusableIndexVec` with a given initial capacity.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_key(capacity)
+    }
+}
+
+impl<T, K: Key> SplitReusableIndexVec<T, K> {
+    #[inline]
+    /// Creates a new empty `SplitReusableIndexVec` that uses the key `K` for its IDs.
+    pub fn with_key() -> Self {
+        Self::with_capacity_and_key(DEFAULT_INITIAL_CAPACITY)
+    }
+
+    #[inline]
+    /// Creates a new empty `SplitReusableIndexVec` that uses the key `K` for its IDs, with a given
+    /// initial capacity.
+    pub fn with_capacity_and_key(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+            last_removed: None,
+            live_count: 0,
+            exhaustion_policy: ExhaustionPolicy::default(),
+            occupied: Occupancy::default(),
+            removed_at: RemovalLog::default(),
+        }
+    }
+
+    #[inline]
+    /// Returns the number of existing elements.
+    pub fn len(&self) -> usize {
+        self.live_count
+    }
+
+    #[inline]
+    /// Returns `true` if there are no existing elements.
+    pub fn is_empty(&self) -> bool {
+        self.live_count == 0
+    }
+
+    #[inline]
+    /// Returns what is done with slots that run out of cycle stamps.
+    pub fn exhaustion_policy(&self) -> ExhaustionPolicy {
+        self.exhaustion_policy
+    }
+
+    #[inline]
+    /// Changes what is done with slots that run out of cycle stamps.
+    pub fn set_exhaustion_policy(&mut self, policy: ExhaustionPolicy) {
+        self.exhaustion_policy = policy;
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Panics
+    /// Panics whenever `try_add` would return an error.
+    pub fn add(&mut self, node: T) -> ID<T, K> {
+        match self.try_add(node) {
+            Ok(id) => id,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Adds a new element, returning a given ID associated with it.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_add`.
+    pub fn try_add(&mut self, node: T) -> Result<ID<T, K>, ReusableIndexAddError<T>> {
+        while let Some(last_removed) = self.last_removed {
+            let new_cycle_stamp = match free_list_head(&self.slots[last_removed], last_removed, self.exhaustion_policy) {
+                Ok((FreeListHead::Reuse(new_cycle_stamp), next_removed)) => {
+                    self.last_removed = next_removed;
+                    new_cycle_stamp
+                },
+                Ok((FreeListHead::Retire(cycle_stamp), next_removed)) => {
+                    // The slot has run out of stamps, it must never be used again.
+                    self.slots[last_removed] = ReusableIndexNode::Retired(cycle_stamp);
+                    self.last_removed = next_removed;
+                    continue;
+                },
+                // The slot stays at the head of the free list, so that every call reports it.
+                Err(error) => return Err(ReusableIndexAddError::new(error, node)),
+            };
+
+            self.slots[last_removed] = ReusableIndexNode::Exists(new_cycle_stamp, ());
+            self.values[last_removed] = MaybeUninit::new(node);
+            self.occupied.set(last_removed);
+            self.live_count += 1;
+            return Ok(ID::from_untyped(K::new(new_cycle_stamp, last_removed)));
+        }
+
+        let added_at_index = self.slots.len();
+        if let Err(error) = check_fresh_index::<K>(added_at_index) {
+            return Err(ReusableIndexAddError::new(error, node));
+        }
+
+        // The value goes in first, so that a slot never says it exists without one.
+        self.values.push(MaybeUninit::new(node));
+        self.slots.push(ReusableIndexNode::Exists(K::Stamp::ZERO, ()));
+        self.occupied.set(added_at_index);
+        self.live_count += 1;
+        Ok(ID::from_untyped(K::new(K::Stamp::ZERO, added_at_index)))
+    }
+
+    /// Removes the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::remove`.
+    #[inline]
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn remove(&mut self, id: ID<T, K>) -> ListResult<()> {
+        self.take(id).map(|_| ())
+    }
+
+    /// Removes the element associated with the given ID, giving it back.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::take`.
+    #[cfg_attr(all(feature = "debug-removals", debug_assertions), track_caller)]
+    pub fn take(&mut self, id: ID<T, K>) -> ListResult<T> {
+        self.check_id(id)?;
+        let (cycle_stamp, index) = (id.stamp(), id.index());
+
+        if self.exhaustion_policy == ExhaustionPolicy::Retire && cycle_stamp == K::MAX_STAMP {
+            self.slots[index] = ReusableIndexNode::Retired(cycle_stamp);
+        } else {
+            self.slots[index] = match self.last_removed {
+                Some(next_removed) => ReusableIndexNode::RemovedAndNext(cycle_stamp, K::Index::from_index(next_removed)),
+                None => ReusableIndexNode::Removed(cycle_stamp),
+            };
+            self.last_removed = Some(index);
+        }
+        self.occupied.clear(index);
+        self.live_count -= 1;
+        #[cfg(all(feature = "debug-removals", debug_assertions))]
+        self.removed_at.record(index);
+
+        // The slot held an element, and is not marked as such anymore, so the value is read once.
+        Ok(unsafe { self.values[index].assume_init_read() })
+    }
+
+    #[inline]
+    fn check_id(&self, id: ID<T, K>) -> ListResult<()> {
+        check_status(id, slot_status(self.slots.get(id.index()), id.stamp(), self.slots.len()), &self.removed_at)
+    }
+
+    #[inline]
+    // Tells whether the ID points to an element, looking at the stamps only.
+    fn is_live(&self, id: ID<T, K>) -> bool {
+        matches!(self.slots.get(id.index()), Some(ReusableIndexNode::Exists(cycle_stamp, _)) if *cycle_stamp == id.stamp())
+    }
+
+    #[inline]
+    /// Returns true if the given ID is associated with an element of the vector.
+    pub fn contains(&self, id: ID<T, K>) -> bool {
+        self.is_live(id)
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get(&self, id: ID<T, K>) -> Option<&T> {
+        if self.is_live(id) {
+            // Live slots hold an initialized value.
+            Some(unsafe { self.values[id.index()].assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// Returns `None` if the element does not exist.
+    pub fn get_mut(&mut self, id: ID<T, K>) -> Option<&mut T> {
+        if self.is_live(id) {
+            // Live slots hold an initialized value.
+            Some(unsafe { self.values[id.index()].assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get`.
+    pub fn try_get(&self, id: ID<T, K>) -> ListResult<&T> {
+        self.check_id(id)?;
+        Ok(self.get(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
+    /// Returns a mutable reference to the element associated with the given ID.
+    ///
+    /// # Errors
+    /// This function returns the same errors as `ReusableIndexVec::try_get_mut`.
+    pub fn try_get_mut(&mut self, id: ID<T, K>) -> ListResult<&mut T> {
+        self.check_id(id)?;
+        Ok(self.get_mut(id).expect("[LOGIC ERROR] Checked node should exist"))
+    }
+
+    #[inline]
+    /// Returns a view of the slots of the vector, for code that works with the nodes of a
+    /// `ReusableIndexVec::as_slice`.
+    pub fn as_slice(&self) -> SplitReusableIndexSlice<'_, T, K> {
+        SplitReusableIndexSlice {
+            slots: &self.slots,
+            values: &self.values,
+        }
+    }
+
+    #[inline]
+    /// Returns an iterator on the list of existing elements.
+    pub fn iter(&self) -> SplitReusableIndexIterator<'_, T, K> {
+        SplitReusableIndexIterator {
+            values: &self.values,
+            occupied: self.occupied.words(),
+            front: 0,
+            back: self.values.len(),
+            remaining: self.live_count,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Returns an iterator of mutable references on the list of existing elements.
+    pub fn iter_mut(&mut self) -> SplitReusableIndexIteratorMut<'_, T, K> {
+        SplitReusableIndexIteratorMut {
+            back: self.values.len(),
+            values: self.values.iter_mut(),
+            occupied: self.occupied.words(),
+            front: 0,
+            remaining: self.live_count,
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Returns an iterator on the list of existing elements, yielding each one with its ID.
+    pub fn iter_with_ids(&self) -> SplitReusableIndexIdIterator<'_, T, K> {
+        SplitReusableIndexIdIterator {
+            slots: &self.slots,
+            values: &self.values,
+            occupied: self.occupied.words(),
+            front: 0,
+            back: self.slots.len(),
+            remaining: self.live_count,
+        }
+    }
+
+    #[inline]
+    /// Returns an iterator of mutable references on the list of existing elements, yielding each
+    /// one with its ID.
+    pub fn iter_mut_with_ids(&mut self) -> SplitReusableIndexIdIteratorMut<'_, T, K> {
+        SplitReusableIndexIdIteratorMut {
+            slots: &self.slots,
+            values: self.values.iter_mut(),
+            occupied: self.occupied.words(),
+            front: 0,
+            back: self.slots.len(),
+            remaining: self.live_count,
+        }
+    }
+} // End of impl SplitReusableIndexVec
+
+impl<T, K: Key> Drop for SplitReusableIndexVec<T, K> {
+    fn drop(&mut self) {
+        for (slot, value) in self.slots.iter().zip(self.values.iter_mut()) {
+            if let ReusableIndexNode::Exists(_, _) = slot {
+                // Live slots hold an initialized value, which is dropped once, here.
+                unsafe { value.assume_init_drop() };
+            }
+        }
+    }
+}
+
+impl<T, K: Key> Default for SplitReusableIndexVec<T, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::with_key()
+    }
+}
+
+impl<T: fmt::Debug, K: Key> fmt::Debug for SplitReusableIndexVec<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+impl<T, K: Key> ops::Index<ID<T, K>> for SplitReusableIndexVec<T, K> {
+    type Output = T;
+
+    fn index(&self, id: ID<T, K>) -> &T {
+        match self.try_get(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for SplitReusableIndexVec: {}", err),
+        }
+    }
+}
+
+impl<T, K: Key> ops::IndexMut<ID<T, K>> for SplitReusableIndexVec<T, K> {
+    fn index_mut(&mut self, id: ID<T, K>) -> &mut T {
+        match self.try_get_mut(id) {
+            Ok(node) => node,
+            Err(err) => panic!("Invalid ID for SplitReusableIndexVec: {}", err),
+        }
+    }
+}
+
+/// A view of the slots of a `SplitReusableIndexVec`, in place of the slice of nodes of a
+/// `ReusableIndexVec`. The nodes are built as they are asked for, holding references to the
+/// values.
+#[derive(Clone, Copy)]
+pub struct SplitReusableIndexSlice<'vec, T, K: Key = UntypedID> {
+    slots: &'vec [Slot<K>],
+    values: &'vec [MaybeUninit<T>],
+}
+
+impl<'vec, T, K: Key> SplitReusableIndexSlice<'vec, T, K> {
+    #[inline]
+    /// Returns the number of slots, existing or not.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    /// Returns `true` if there are no slots.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    #[inline]
+    /// Returns the node at `index`, or `None` if it is out of bounds.
+    pub fn get(&self, index: Index) -> Option<ReusableIndexNode<&'vec T, K>> {
+        Some(Self::node(self.slots.get(index)?, &self.values[index]))
+    }
+
+    #[inline]
+    /// Returns an iterator over the nodes, in order.
+    pub fn iter(&self) -> SplitReusableIndexSliceIter<'vec, T, K> {
+        SplitReusableIndexSliceIter {
+            inner: self.slots.iter().zip(self.values.iter()),
+        }
+    }
+
+    /// Copies the nodes into the layout of a `ReusableIndexVec`.
+    pub fn to_vec(&self) -> Vec<ReusableIndexNode<T, K>>
+    where T: Clone {
+        self.iter().map(|node| match node {
+            ReusableIndexNode::Exists(cycle_stamp, value) => ReusableIndexNode::Exists(cycle_stamp, value.clone()),
+            ReusableIndexNode::Removed(cycle_stamp) => ReusableIndexNode::Removed(cycle_stamp),
+            ReusableIndexNode::RemovedAndNext(cycle_stamp, next) => ReusableIndexNode::RemovedAndNext(cycle_stamp, next),
+            ReusableIndexNode::Retired(cycle_stamp) => ReusableIndexNode::Retired(cycle_stamp),
+            ReusableIndexNode::Reserved(cycle_stamp) => ReusableIndexNode::Reserved(cycle_stamp),
+        }).collect()
+    }
+
+    fn node(slot: &Slot<K>, value: &'vec MaybeUninit<T>) -> ReusableIndexNode<&'vec T, K> {
+        match *slot {
+            // Live slots hold an initialized value.
+            ReusableIndexNode::Exists(cycle_stamp, ()) => ReusableIndexNode::Exists(cycle_stamp, unsafe { value.assume_init_ref() }),
+            ReusableIndexNode::Removed(cycle_stamp) => ReusableIndexNode::Removed(cycle_stamp),
+            ReusableIndexNode::RemovedAndNext(cycle_stamp, next) => ReusableIndexNode::RemovedAndNext(cycle_stamp, next),
+            ReusableIndexNode::Retired(cycle_stamp) => ReusableIndexNode::Retired(cycle_stamp),
+            ReusableIndexNode::Reserved(cycle_stamp) => ReusableIndexNode::Reserved(cycle_stamp),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> IntoIterator for SplitReusableIndexSlice<'vec, T, K> {
+    type Item = ReusableIndexNode<&'vec T, K>;
+    type IntoIter = SplitReusableIndexSliceIter<'vec, T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the nodes of a `SplitReusableIndexSlice`.
+pub struct SplitReusableIndexSliceIter<'vec, T, K: Key = UntypedID> {
+    inner: SlotsAndValues<'vec, T, K>,
+}
+
+impl<'vec, T, K: Key> Iterator for SplitReusableIndexSliceIter<'vec, T, K> {
+    type Item = ReusableIndexNode<&'vec T, K>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(slot, value)| SplitReusableIndexSlice::node(slot, value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for SplitReusableIndexSliceIter<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for SplitReusableIndexSliceIter<'vec, T, K> {}
+
+/// An iterator over the IDs of the existing elements of a `SplitReusableIndexVec`, together with
+/// references to them.
+pub struct SplitReusableIndexIdIterator<'vec, T, K: Key = UntypedID> {
+    slots: &'vec [Slot<K>],
+    values: &'vec [MaybeUninit<T>],
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> SplitReusableIndexIdIterator<'vec, T, K> {
+    #[inline]
+    fn entry_at(&mut self, index: Index) -> (ID<T, K>, &'vec T) {
+        match self.slots[index] {
+            ReusableIndexNode::Exists(cycle_stamp, ()) => {
+                self.remaining -= 1;
+                // Live slots hold an initialized value.
+                (ID::from_untyped(K::new(cycle_stamp, index)), unsafe { self.values[index].assume_init_ref() })
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for SplitReusableIndexIdIterator<'vec, T, K> {
+    type Item = (ID<T, K>, &'vec T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        self.front = index + 1;
+
+        Some(self.entry_at(index))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for SplitReusableIndexIdIterator<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        self.back = index;
+
+        Some(self.entry_at(index))
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for SplitReusableIndexIdIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for SplitReusableIndexIdIterator<'vec, T, K> {}
+
+/// An iterator over the IDs of the existing elements of a `SplitReusableIndexVec`, together with
+/// mutable references to them.
+pub struct SplitReusableIndexIdIteratorMut<'vec, T, K: Key = UntypedID> {
+    slots: &'vec [Slot<K>],
+    // Holds the values from `front` up to, not including, `back`.
+    values: slice::IterMut<'vec, MaybeUninit<T>>,
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+}
+
+impl<'vec, T, K: Key> SplitReusableIndexIdIteratorMut<'vec, T, K> {
+    #[inline]
+    fn entry_at(&mut self, index: Index, value: Option<&'vec mut MaybeUninit<T>>) -> (ID<T, K>, &'vec mut T) {
+        match (&self.slots[index], value) {
+            (ReusableIndexNode::Exists(cycle_stamp, ()), Some(value)) => {
+                self.remaining -= 1;
+                // Live slots hold an initialized value.
+                (ID::from_untyped(K::new(*cycle_stamp, index)), unsafe { value.assume_init_mut() })
+            },
+            _ => unreachable!("[LOGIC ERROR] Slot {} is marked as occupied but holds no element", index),
+        }
+    }
+}
+
+impl<'vec, T, K: Key> Iterator for SplitReusableIndexIdIteratorMut<'vec, T, K> {
+    type Item = (ID<T, K>, &'vec mut T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        let value = self.values.nth(index - self.front);
+        self.front = index + 1;
+
+        Some(self.entry_at(index, value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for SplitReusableIndexIdIteratorMut<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        let value = self.values.nth_back(self.back - 1 - index);
+        self.back = index;
+
+        Some(self.entry_at(index, value))
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for SplitReusableIndexIdIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for SplitReusableIndexIdIteratorMut<'vec, T, K> {}
+
+/// An iterator over references to the existing elements of a `SplitReusableIndexVec`.
+///
+/// Only the occupancy bits and the values are read, never the stamps.
+pub struct SplitReusableIndexIterator<'vec, T, K: Key = UntypedID> {
+    values: &'vec [MaybeUninit<T>],
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+    marker: PhantomData<K>,
+}
+
+impl<'vec, T, K: Key> Iterator for SplitReusableIndexIterator<'vec, T, K> {
+    type Item = &'vec T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        self.front = index + 1;
+        self.remaining -= 1;
+
+        // Occupied slots hold an initialized value.
+        Some(unsafe { self.values[index].assume_init_ref() })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for SplitReusableIndexIterator<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        self.back = index;
+        self.remaining -= 1;
+
+        // Occupied slots hold an initialized value.
+        Some(unsafe { self.values[index].assume_init_ref() })
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for SplitReusableIndexIterator<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for SplitReusableIndexIterator<'vec, T, K> {}
+
+/// An iterator over mutable references to the existing elements of a `SplitReusableIndexVec`.
+///
+/// Only the occupancy bits and the values are read, never the stamps.
+pub struct SplitReusableIndexIteratorMut<'vec, T, K: Key = UntypedID> {
+    // Holds the values from `front` up to, not including, `back`.
+    values: slice::IterMut<'vec, MaybeUninit<T>>,
+    occupied: &'vec [u64],
+    front: Index,
+    back: Index,
+    remaining: usize,
+    marker: PhantomData<K>,
+}
+
+impl<'vec, T, K: Key> Iterator for SplitReusableIndexIteratorMut<'vec, T, K> {
+    type Item = &'vec mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = match next_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.front = self.back;
+                return None;
+            },
+        };
+        let value = self.values.nth(index - self.front).expect("[LOGIC ERROR] Occupied slot should have a value");
+        self.front = index + 1;
+        self.remaining -= 1;
+
+        // Occupied slots hold an initialized value.
+        Some(unsafe { value.assume_init_mut() })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'vec, T, K: Key> DoubleEndedIterator for SplitReusableIndexIteratorMut<'vec, T, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = match prev_occupied(self.occupied, self.front, self.back) {
+            Some(index) => index,
+            None => {
+                self.back = self.front;
+                return None;
+            },
+        };
+        let value = self.values.nth_back(self.back - 1 - index).expect("[LOGIC ERROR] Occupied slot should have a value");
+        self.back = index;
+        self.remaining -= 1;
+
+        // Occupied slots hold an initialized value.
+        Some(unsafe { value.assume_init_mut() })
+    }
+}
+
+impl<'vec, T, K: Key> ExactSizeIterator for SplitReusableIndexIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> FusedIterator for SplitReusableIndexIteratorMut<'vec, T, K> {}
+
+impl<'vec, T, K: Key> IntoIterator for &'vec SplitReusableIndexVec<T, K> {
+    type Item = &'vec T;
+    type IntoIter = SplitReusableIndexIterator<'vec, T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'vec, T, K: Key> IntoIterator for &'vec mut SplitReusableIndexVec<T, K> {
+    type Item = &'vec mut T;
+    type IntoIter = SplitReusableIndexIteratorMut<'vec, T, K>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorType as BugeErrorType;
+    use crate::list::{PackedID, ReusableIndexVec};
+    use std::mem;
+    use std::rc::Rc;
+
+    #[test]
+    fn split_test() {
+        let mut split = SplitReusableIndexVec::new();
+        let mut plain = ReusableIndexVec::new();
+
+        // Both vectors hand out the same IDs and keep the same nodes.
+        let mut ids = Vec::new();
+        for i in 0..10u128 {
+            let id = split.add(i);
+            assert_eq!(id.untyped(), plain.add(i).untyped());
+            ids.push(id);
+        }
+        for id in [ids[3], ids[7], ids[5]].iter() {
+            assert_eq!(split.take(*id).unwrap(), id.index() as u128);
+            plain.remove(id.untyped().typed()).unwrap();
+        }
+        for i in 10..12u128 {
+            assert_eq!(split.add(i).untyped(), plain.add(i).untyped());
+        }
+        assert_eq!(split.as_slice().to_vec().len(), plain.as_slice().len());
+        assert_eq!(format!("{:?}", split.as_slice().to_vec()), format!("{:?}", plain.as_slice()));
+
+        assert_eq!(split.len(), 9);
+        assert_eq!(split.iter().copied().collect::<Vec<_>>(), plain.iter().copied().collect::<Vec<_>>());
+        assert_eq!(split.iter_with_ids().size_hint(), (9, Some(9)));
+
+        // Old IDs are told apart by the stamps.
+        assert_eq!(split.get(ids[3]), None);
+        assert_eq!(split.try_get(ids[3]).unwrap_err().error_type(), BugeErrorType::Expired);
+        assert_eq!(split.try_get(ID::from_untyped(UntypedID(0, 99))).unwrap_err().error_type(), BugeErrorType::NotFound);
+        assert_eq!(split.remove(ids[7]).unwrap_err().error_type(), BugeErrorType::Expired);
+
+        for (id, value) in split.iter_mut_with_ids() {
+            *value += id.index() as u128;
+        }
+        assert_eq!(split[ids[9]], 18);
+        split[ids[9]] = 0;
+        assert_eq!(split.get(ids[9]), Some(&0));
+
+        // A `u128` takes exactly its size among the values.
+        assert_eq!(mem::size_of::<MaybeUninit<u128>>(), mem::size_of::<u128>());
+        assert!(mem::size_of::<Slot<UntypedID>>() < mem::size_of::<ReusableIndexNode<u128>>());
+    }
+
+    #[test]
+    fn split_sparse_iteration_test() {
+        let mut split = SplitReusableIndexVec::new();
+        let ids: Vec<_> = (0..300u32).map(|i| split.add(i)).collect();
+        for id in ids[1..130].iter().chain(&ids[140..299]) {
+            split.remove(*id).unwrap();
+        }
+
+        // The runs of removed slots are skipped from either end.
+        let expected: Vec<u32> = [0].iter().copied().chain(130..140).chain([299].iter().copied()).collect();
+        assert_eq!(split.iter().copied().collect::<Vec<_>>(), expected);
+        assert_eq!(split.iter().rev().copied().collect::<Vec<_>>(), expected.iter().rev().copied().collect::<Vec<_>>());
+
+        let mut iter = split.iter_mut_with_ids();
+        assert_eq!(iter.next_back().map(|(id, _)| id), Some(ids[299]));
+        assert_eq!(iter.next().map(|(id, _)| id), Some(ids[0]));
+        for (_, value) in &mut iter {
+            *value += 1000;
+        }
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+        assert_eq!(split.iter().filter(|value| **value >= 1000).count(), 10);
+    }
+
+    #[test]
+    fn split_value_iteration_test() {
+        let mut split = SplitReusableIndexVec::new();
+        let ids: Vec<_> = (0..200u32).map(|i| split.add(i)).collect();
+        for id in ids.iter().filter(|id| id.index() % 7 < 3 || (60..150).contains(&id.index())) {
+            split.remove(*id).unwrap();
+        }
+        split.add(1000);
+
+        // The value iterators see the same elements as the ones that read the stamps for the IDs.
+        let with_ids: Vec<u32> = split.iter_with_ids().map(|(_, value)| *value).collect();
+        assert_eq!(split.iter().copied().collect::<Vec<_>>(), with_ids);
+        assert_eq!(split.iter().len(), with_ids.len());
+        assert_eq!(split.iter().rev().copied().collect::<Vec<_>>(), with_ids.iter().rev().copied().collect::<Vec<_>>());
+
+        let mut iter = split.iter_mut();
+        assert_eq!(iter.next_back().copied(), with_ids.last().copied());
+        assert_eq!(iter.next().copied(), with_ids.first().copied());
+        for value in iter {
+            *value += 1;
+        }
+        let with_ids_mut: Vec<u32> = split.iter_mut_with_ids().map(|(_, value)| *value).collect();
+        assert_eq!(split.iter().copied().collect::<Vec<_>>(), with_ids_mut);
+        assert_eq!(with_ids_mut.iter().zip(&with_ids).filter(|(a, b)| a != b).count(), with_ids.len() - 2);
+    }
+
+    #[test]
+    fn split_drop_test() {
+        let counter = Rc::new(());
+        let mut split = SplitReusableIndexVec::with_key();
+
+        let ids: Vec<ID<Rc<()>, PackedID>> = (0..8).map(|_| split.add(Rc::clone(&counter))).collect();
+        assert_eq!(Rc::strong_count(&counter), 9);
+
+        split.remove(ids[2]).unwrap();
+        drop(split.take(ids[4]).unwrap());
+        assert_eq!(Rc::strong_count(&counter), 7);
+
+        // Only the values that exist are dropped with the vector.
+        drop(split);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn split_exhaustion_test() {
+        let mut split = SplitReusableIndexVec::<u8, PackedID>::with_key();
+        split.set_exhaustion_policy(ExhaustionPolicy::Retire);
+
+        let mut id = split.add(0);
+        while id.stamp() < PackedID::MAX_STAMP {
+            split.remove(id).unwrap();
+            id = split.add(0);
+        }
+        split.remove(id).unwrap();
+
+        // The exhausted slot is not reused.
+        let new_id = split.add(1);
+        assert_eq!(new_id.index(), 1);
+        assert!(matches!(split.as_slice().get(0), Some(ReusableIndexNode::Retired(_))));
+    }
+}